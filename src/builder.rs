@@ -1,26 +1,58 @@
-use regex::Regex;
+use regex::{Captures, Regex};
 
+use crate::{lint::Warning, tokenizer::Position};
+
+/// Strips comments while keeping every token on its original line and column,
+/// so positions reported by the tokenizer match the source file.
 pub fn build_content(content: String) -> String {
     let mut code_lines: Vec<String> = Vec::new();
 
     let content = clear_special_coments(content);
 
     for line in content.lines() {
+        let indentation = &line[..line.len() - line.trim_start().len()];
         let line = clean_line(line);
 
-        if line.len() == 0 {
+        if line.is_empty() {
+            code_lines.push(line);
             continue;
         }
 
-        code_lines.push(String::from(line));
+        code_lines.push(format!("{}{}", indentation, line));
     }
 
-    code_lines.join("")
+    code_lines.join("\n")
+}
+
+/// Warns about raw source lines whose indentation mixes tabs and spaces, which
+/// looks different in every editor.
+pub fn lint_indentation(content: &str) -> Vec<Warning> {
+    content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| {
+            let indentation = &line[..line.len() - line.trim_start_matches([' ', '\t']).len()];
+
+            indentation.contains(' ') && indentation.contains('\t')
+        })
+        .map(|(index, _)| {
+            Warning::new(
+                "indentation mixes tabs and spaces",
+                Position::new(index + 1, 1),
+            )
+        })
+        .collect()
 }
 
 fn clear_special_coments(content: String) -> String {
     let re = Regex::new(r"/\*(.|\r\n|\r|\n)*?\*/").unwrap();
-    re.replace_all(&content.as_str(), "").to_string()
+    re.replace_all(content.as_str(), |captures: &Captures| {
+        captures[0]
+            .chars()
+            .map(|c| if c == '\r' || c == '\n' { c } else { ' ' })
+            .collect::<String>()
+    })
+    .to_string()
 }
 
 fn clean_line(line: &str) -> String {
@@ -52,7 +84,7 @@ mod tests {
         let clean_code = clear_special_coments(String::from(
             "   test(x);    /** should test with coment */",
         ));
-        let token = clean_line(&clean_code.as_str());
+        let token = clean_line(clean_code.as_str());
 
         assert_eq!("test(x);", token);
     }
@@ -62,8 +94,47 @@ mod tests {
         let clean_code = clear_special_coments(String::from(
             "   test(x);    /** should test with coment \r\n * test \r\n * another test \r\n * end test */ \r\n antoherTest();"));
 
-        let token = clean_line(&clean_code.as_str());
+        let token = clean_line(clean_code.as_str());
+
+        assert_eq!(
+            format!(
+                "test(x);{}\r\n{}\r\n{}\r\n{}\r\n antoherTest();",
+                " ".repeat(32),
+                " ".repeat(8),
+                " ".repeat(16),
+                " ".repeat(15)
+            ),
+            token
+        );
+    }
+
+    #[test]
+    fn lint_mixed_indentation() {
+        let warnings = lint_indentation(
+            "class Main {\n\tfunction void main() {\n\t    return;\n    \t}\n    }\n}",
+        );
+
+        assert_eq!(
+            warnings
+                .iter()
+                .map(|warning| warning.to_string())
+                .collect::<Vec<String>>(),
+            vec![
+                "3:1: indentation mixes tabs and spaces",
+                "4:1: indentation mixes tabs and spaces",
+            ]
+        );
+    }
+
+    #[test]
+    fn build_content_keeps_lines_and_columns() {
+        let content = build_content(String::from(
+            "/** doc\n * comment */\nclass Main { // main class\n    /* inline */ field int x;\n}",
+        ));
 
-        assert_eq!("test(x);     \r\n antoherTest();", token);
+        assert_eq!(
+            "\n\nclass Main {\n                 field int x;\n}",
+            content
+        );
     }
 }
@@ -0,0 +1,224 @@
+use std::convert::TryInto;
+
+use crate::error::CompileError;
+use crate::tokenizer::{Limits, Position, TokenItem, TokenType, Tokenizer};
+
+const MAGIC: &[u8; 4] = b"JKTC";
+const VERSION: u8 = 3;
+
+/// The tokenizer settings a cache is written with. Tokens read with another
+/// tab width have other columns, and other limits may reject them, so a cache
+/// only matches the settings it was written with.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CacheSettings {
+    pub tab_width: usize,
+    pub limits: Limits,
+}
+
+pub fn serialize_tokens(tokenizer: &Tokenizer, settings: CacheSettings) -> Vec<u8> {
+    let mut result: Vec<u8> = Vec::new();
+
+    result.extend_from_slice(MAGIC);
+    result.push(VERSION);
+    write_u32(&mut result, settings.tab_width);
+    write_u32(&mut result, settings.limits.max_line_length);
+    write_u32(&mut result, settings.limits.max_token_length);
+    write_u32(&mut result, tokenizer.get_tokens().len());
+
+    for token in tokenizer.get_tokens() {
+        let value = token.get_value();
+
+        result.push(type_to_byte(token.get_type()));
+        write_u32(&mut result, token.get_position().get_line());
+        write_u32(&mut result, token.get_position().get_column());
+        write_u32(&mut result, value.len());
+        result.extend_from_slice(value.as_bytes());
+    }
+
+    result
+}
+
+pub fn deserialize_tokens(
+    bytes: &[u8],
+    settings: CacheSettings,
+) -> Result<Tokenizer, CompileError> {
+    let mut reader = Reader { bytes, position: 0 };
+
+    if reader.read(MAGIC.len())? != MAGIC {
+        return Err(invalid_cache("missing header"));
+    }
+
+    let version = reader.read(1)?[0];
+    if version != VERSION {
+        return Err(invalid_cache(
+            format!("unsupported version {}", version).as_str(),
+        ));
+    }
+
+    let cached = CacheSettings {
+        tab_width: reader.read_u32()?,
+        limits: Limits {
+            max_line_length: reader.read_u32()?,
+            max_token_length: reader.read_u32()?,
+        },
+    };
+    if cached != settings {
+        return Err(invalid_cache("written with other tokenizer settings"));
+    }
+
+    let count = reader.read_u32()?;
+    let mut tokens = Vec::new();
+
+    for _ in 0..count {
+        let token_type = byte_to_type(reader.read(1)?[0])?;
+        let position = Position::new(reader.read_u32()?, reader.read_u32()?);
+        let length = reader.read_u32()?;
+        let value = String::from_utf8(reader.read(length)?.to_vec())
+            .map_err(|_| invalid_cache("token value is not valid UTF-8"))?;
+
+        tokens.push(TokenItem::new(value.as_str(), token_type).with_position(position));
+    }
+
+    Ok(Tokenizer::from_tokens(tokens))
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn read(&mut self, length: usize) -> Result<&'a [u8], CompileError> {
+        let end = self.position + length;
+
+        if end > self.bytes.len() {
+            return Err(invalid_cache("unexpected end of data"));
+        }
+
+        let result = &self.bytes[self.position..end];
+        self.position = end;
+
+        Ok(result)
+    }
+
+    fn read_u32(&mut self) -> Result<usize, CompileError> {
+        let bytes: [u8; 4] = self.read(4)?.try_into().unwrap();
+        Ok(u32::from_le_bytes(bytes) as usize)
+    }
+}
+
+fn write_u32(result: &mut Vec<u8>, value: usize) {
+    result.extend_from_slice(&(value as u32).to_le_bytes());
+}
+
+fn invalid_cache(message: &str) -> CompileError {
+    CompileError::InvalidTokenCache {
+        message: String::from(message),
+    }
+}
+
+fn type_to_byte(value: TokenType) -> u8 {
+    match value {
+        TokenType::String => 0,
+        TokenType::Integer => 1,
+        TokenType::Symbol => 2,
+        TokenType::Identifier => 3,
+        TokenType::Keyword => 4,
+        TokenType::None => 5,
+    }
+}
+
+fn byte_to_type(value: u8) -> Result<TokenType, CompileError> {
+    match value {
+        0 => Ok(TokenType::String),
+        1 => Ok(TokenType::Integer),
+        2 => Ok(TokenType::Symbol),
+        3 => Ok(TokenType::Identifier),
+        4 => Ok(TokenType::Keyword),
+        5 => Ok(TokenType::None),
+        v => Err(invalid_cache(format!("unknown token type {}", v).as_str())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::ClassNode;
+    use crate::writer::VmWriter;
+
+    fn settings() -> CacheSettings {
+        CacheSettings {
+            tab_width: 1,
+            limits: Limits::default(),
+        }
+    }
+
+    #[test]
+    fn round_trip_tokens() {
+        let source = "class Main { function void main() { var String s; let s = \"a b\"; do Output.printInt(-12 + 3); return; } }";
+        let tokenizer = Tokenizer::new(source);
+
+        let loaded =
+            deserialize_tokens(&serialize_tokens(&tokenizer, settings()), settings()).unwrap();
+
+        assert_eq!(loaded.get_tokens(), tokenizer.get_tokens());
+
+        let code: Vec<String> = VmWriter::new().build(&ClassNode::build(&tokenizer).unwrap());
+        let loaded_code: Vec<String> = VmWriter::new().build(&ClassNode::build(&loaded).unwrap());
+
+        assert_eq!(loaded_code, code);
+    }
+
+    #[test]
+    fn deserialize_truncated_data() {
+        let tokenizer = Tokenizer::new("let x = 1;");
+        let bytes = serialize_tokens(&tokenizer, settings());
+
+        let result = deserialize_tokens(&bytes[..bytes.len() - 1], settings());
+
+        assert_eq!(
+            result.err(),
+            Some(CompileError::InvalidTokenCache {
+                message: String::from("unexpected end of data")
+            })
+        );
+    }
+
+    #[test]
+    fn deserialize_invalid_header() {
+        let result = deserialize_tokens(b"nope", settings());
+
+        assert_eq!(
+            result.err(),
+            Some(CompileError::InvalidTokenCache {
+                message: String::from("missing header")
+            })
+        );
+    }
+
+    #[test]
+    fn deserialize_with_other_settings() {
+        let tokenizer = Tokenizer::new("let x = 1;");
+        let bytes = serialize_tokens(&tokenizer, settings());
+        let tab_width = CacheSettings {
+            tab_width: 4,
+            ..settings()
+        };
+        let limits = CacheSettings {
+            limits: Limits {
+                max_token_length: 10,
+                ..Limits::default()
+            },
+            ..settings()
+        };
+
+        for other in [tab_width, limits] {
+            assert_eq!(
+                deserialize_tokens(&bytes, other).err(),
+                Some(CompileError::InvalidTokenCache {
+                    message: String::from("written with other tokenizer settings")
+                })
+            );
+        }
+    }
+}
@@ -0,0 +1,63 @@
+use std::io::{self, IsTerminal};
+
+const RED: &str = "\x1b[31m";
+const YELLOW: &str = "\x1b[33m";
+const DIM: &str = "\x1b[2m";
+const RESET: &str = "\x1b[0m";
+
+/// Whether diagnostics get colored: `Some` when forced by `--color` or
+/// `--no-color`, otherwise only when stderr, where they are printed, is a
+/// terminal.
+pub fn use_color(choice: Option<bool>) -> bool {
+    choice.unwrap_or_else(|| io::stderr().is_terminal())
+}
+
+/// Paints the `error:` label of a diagnostic line red, or its `warning:`
+/// label yellow, and dims the location written before it.
+pub fn colorize(line: &str) -> String {
+    for (label, color) in [("error:", RED), ("warning:", YELLOW)] {
+        if let Some(index) = line.find(label) {
+            let (location, rest) = line.split_at(index);
+            let location = if location.is_empty() {
+                String::new()
+            } else {
+                format!("{}{}{}", DIM, location, RESET)
+            };
+
+            return format!(
+                "{}{}{}{}{}",
+                location,
+                color,
+                label,
+                RESET,
+                &rest[label.len()..]
+            );
+        }
+    }
+
+    String::from(line)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn colorize_labels() {
+        assert_eq!(
+            colorize("Main.jack: error: 1:2: expected ';'"),
+            "\x1b[2mMain.jack: \x1b[0m\x1b[31merror:\x1b[0m 1:2: expected ';'"
+        );
+        assert_eq!(
+            colorize("warning: skipped Square"),
+            "\x1b[33mwarning:\x1b[0m skipped Square"
+        );
+        assert_eq!(colorize("tokenize  1ms"), "tokenize  1ms");
+    }
+
+    #[test]
+    fn forced_choice_wins() {
+        assert!(use_color(Some(true)));
+        assert!(!use_color(Some(false)));
+    }
+}
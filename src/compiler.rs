@@ -0,0 +1,401 @@
+use std::cell::RefCell;
+use std::fs;
+use std::io;
+
+use crate::{
+    builder::build_content,
+    diagnostics::Diagnostics,
+    error::CompileError,
+    lint::Warning,
+    options::Options,
+    output::OutputSink,
+    parser::{check_braces, ClassNode, Expression, Statement, SymbolTable, TokenTreeItem, VarDec},
+    tokenizer::{TokenItem, TokenType, Tokenizer, DEFAULT_TAB_WIDTH},
+    vm::{compact, labels_manifest, split_files},
+    writer::VmWriter,
+};
+
+/// Writes `--verbose` messages to stderr, keeping a copy of each one.
+pub struct Logger {
+    verbose: bool,
+    messages: RefCell<Vec<String>>,
+}
+
+impl Logger {
+    pub fn new(verbose: bool) -> Logger {
+        Logger {
+            verbose,
+            messages: RefCell::new(Vec::new()),
+        }
+    }
+
+    pub fn log(&self, filename: &str, message: &str) {
+        if !self.verbose {
+            return;
+        }
+
+        let line = format!("{}: {}", filename, message);
+        eprintln!("{}", line);
+        self.messages.borrow_mut().push(line);
+    }
+
+    pub fn get_messages(&self) -> Vec<String> {
+        self.messages.borrow().clone()
+    }
+}
+
+/// Reads a source file, describing why it could not be read.
+pub fn read_source(filename: &str) -> Result<String, String> {
+    fs::read_to_string(filename).map_err(|error| match error.kind() {
+        io::ErrorKind::InvalidData => format!("file is not valid UTF-8: {}", filename),
+        _ => format!("could not read {}: {}", filename, error),
+    })
+}
+
+pub fn tokenize_source(
+    filename: &str,
+    content: String,
+    options: &Options,
+    logger: &Logger,
+) -> Result<Tokenizer, CompileError> {
+    let clean_code = build_content(content);
+    logger.log(
+        filename,
+        format!("cleaned content: {} lines", clean_code.lines().count()).as_str(),
+    );
+
+    let tokenizer = Tokenizer::with_limits(&clean_code, options.tab_width, options.limits)?;
+    logger.log(
+        filename,
+        format!("tokenized: {} tokens", tokenizer.get_tokens().len()).as_str(),
+    );
+
+    Ok(tokenizer)
+}
+
+pub fn parse_tokens(
+    filename: &str,
+    tokenizer: &Tokenizer,
+    options: &Options,
+    logger: &Logger,
+) -> Result<TokenTreeItem, Diagnostics> {
+    let mut diagnostics = Diagnostics::new(options.max_errors);
+
+    match ClassNode::build_recovering(tokenizer, &mut diagnostics) {
+        Some(root) => {
+            logger.log(
+                filename,
+                format!("parsed: {} tree nodes", root.count_nodes()).as_str(),
+            );
+
+            Ok(root)
+        }
+        None => Err(diagnostics),
+    }
+}
+
+/// Generates the VM code of a class, along with the writer's warnings.
+pub fn write_vm(
+    filename: &str,
+    tree: &TokenTreeItem,
+    options: &Options,
+    logger: &Logger,
+) -> (Vec<String>, Vec<Warning>) {
+    let mut writer = VmWriter::with_options(options.writer_options.clone());
+    let code = writer.build(tree);
+
+    logger.log(
+        filename,
+        format!("generated: {} VM lines", code.len()).as_str(),
+    );
+
+    (code, writer.get_warnings().clone())
+}
+
+/// Sends the files generated for `filename` to `sink`: its `.vm` and, when
+/// asked for, the per-function files and the labels manifest.
+pub fn write_outputs(
+    filename: &str,
+    code: &[String],
+    options: &Options,
+    sink: &mut dyn OutputSink,
+) {
+    let output = if options.compact {
+        compact(code)
+    } else {
+        code.join("\r\n")
+    };
+
+    sink.write(filename.replace(".jack", ".vm").as_str(), output.as_str());
+
+    if options.split {
+        for (path, contents) in split_files(filename, code) {
+            sink.write(path.as_str(), contents.as_str());
+        }
+    }
+
+    if options.labels {
+        sink.write(
+            filename.replace(".jack", ".labels").as_str(),
+            labels_manifest(code).join("\r\n").as_str(),
+        );
+    }
+}
+
+/// Compiles a snippet outside of any class: `var` declarations (added to
+/// `symbol_table`, writing no code), statements, or a single expression.
+pub fn compile_statement(
+    source: &str,
+    symbol_table: &mut SymbolTable,
+    writer: &mut VmWriter,
+) -> Result<Vec<String>, CompileError> {
+    let tokenizer = Tokenizer::with_tab_width(source, DEFAULT_TAB_WIDTH)?;
+    check_braces(tokenizer.get_tokens())?;
+
+    let mut result = Vec::new();
+
+    let is_statement = |value: &str| ["let", "do", "if", "while", "return"].contains(&value);
+
+    while let Some(token) = tokenizer.peek_next() {
+        let tree = match token.get_value().as_str() {
+            "var" => {
+                VarDec::build_var(&tokenizer, symbol_table)?;
+                continue;
+            }
+            value if is_statement(value) => Statement::build(&tokenizer)?,
+            _ => {
+                let tree = Expression::build(&tokenizer)?;
+
+                if let Some(extra) = tokenizer.peek_next() {
+                    return Err(CompileError::syntax(
+                        format!("unexpected '{}' after expression", extra.get_value()).as_str(),
+                        extra.get_position(),
+                    ));
+                }
+
+                tree
+            }
+        };
+
+        if let Some(name) = find_undeclared(&tree, symbol_table) {
+            return Err(CompileError::syntax(
+                format!("'{}' is not declared", name.get_value()).as_str(),
+                name.get_position(),
+            ));
+        }
+
+        writer.set_symbol_table(symbol_table.clone());
+        result.extend(writer.build(&tree));
+    }
+
+    Ok(result)
+}
+
+/// A snippet has no class around it, so every variable it reads or assigns
+/// must be one of the declared locals; finds the first one that is not.
+fn find_undeclared(tree: &TokenTreeItem, symbol_table: &SymbolTable) -> Option<TokenItem> {
+    let nodes = tree.get_nodes();
+    let item_at = |index: usize| nodes.get(index).and_then(|node| node.get_item().as_ref());
+
+    let variable = match tree.get_name().as_deref() {
+        Some("letStatement") => item_at(1),
+        Some("term") => match (item_at(0), nodes.len()) {
+            (Some(item), 1) if item.get_type() == TokenType::Identifier => Some(item),
+            (Some(item), 4)
+                if item.get_type() == TokenType::Identifier
+                    && item_at(1).is_some_and(|symbol| symbol.get_value() == "[") =>
+            {
+                Some(item)
+            }
+            _ => None,
+        },
+        _ => None,
+    };
+
+    if let Some(variable) = variable {
+        if !symbol_table.contains(variable.get_value().as_str()) {
+            return Some(variable.clone());
+        }
+    }
+
+    nodes
+        .iter()
+        .find_map(|node| find_undeclared(node, symbol_table))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::MemorySink;
+
+    #[test]
+    fn verbose_summary_matches_pipeline() {
+        let options =
+            Options::parse(&[String::from("jack_compiler"), String::from("Main.jack")]).unwrap();
+        let logger = Logger::new(true);
+        let content = String::from(
+            "// entry point\r\nclass Main {\r\n    function void main() {\r\n        return;\r\n    }\r\n}",
+        );
+
+        let tokenizer = tokenize_source("Main.jack", content, &options, &logger).unwrap();
+        let tree = parse_tokens("Main.jack", &tokenizer, &options, &logger).unwrap();
+        let (code, warnings) = write_vm("Main.jack", &tree, &options, &logger);
+
+        assert_eq!(tokenizer.get_tokens().len(), 13);
+        assert_eq!(tree.count_nodes(), 19);
+        assert_eq!(code.len(), 3);
+        assert!(warnings.is_empty());
+        assert_eq!(
+            logger.get_messages(),
+            vec![
+                "Main.jack: cleaned content: 6 lines",
+                "Main.jack: tokenized: 13 tokens",
+                "Main.jack: parsed: 19 tree nodes",
+                "Main.jack: generated: 3 VM lines",
+            ]
+        );
+    }
+
+    #[test]
+    fn fast_pipeline_writes_the_same_code() {
+        let compile = |args: &[&str]| {
+            let args: Vec<String> = args.iter().map(|arg| String::from(*arg)).collect();
+            let options = Options::parse(&args).unwrap();
+            let logger = Logger::new(false);
+            let content = String::from(
+                "class Main {\r\n    function void main() {\r\n        var int i;\r\n        while (i < 10) { let i = i + 1; }\r\n        do Output.printInt(i);\r\n        return;\r\n    }\r\n}",
+            );
+
+            let tokenizer = tokenize_source("Main.jack", content, &options, &logger).unwrap();
+            let tree = parse_tokens("Main.jack", &tokenizer, &options, &logger).unwrap();
+
+            write_vm("Main.jack", &tree, &options, &logger)
+        };
+
+        let (code, warnings) = compile(&["jack_compiler", "Main.jack"]);
+        let (fast_code, fast_warnings) = compile(&["jack_compiler", "Main.jack", "--fast"]);
+
+        assert_eq!(fast_code, code);
+        assert!(warnings.is_empty());
+        assert!(fast_warnings.is_empty());
+    }
+
+    #[test]
+    fn tokenize_source_reports_long_lines() {
+        let options = Options::parse(&[
+            String::from("jack_compiler"),
+            String::from("Main.jack"),
+            String::from("--max-line-length"),
+            String::from("20"),
+        ])
+        .unwrap();
+        let logger = Logger::new(false);
+        let content = String::from("class Main {\r\n    function void main() { return; }\r\n}");
+
+        let result = tokenize_source("Main.jack", content, &options, &logger);
+
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "2:1: line is 36 characters long, more than the limit of 20"
+        );
+    }
+
+    #[test]
+    fn write_outputs_to_memory() {
+        let options = Options::parse(&[
+            String::from("jack_compiler"),
+            String::from("Main.jack"),
+            String::from("--labels"),
+        ])
+        .unwrap();
+        let logger = Logger::new(false);
+        let content = String::from("class Main { function void main() { return; } }");
+
+        let tokenizer = tokenize_source("Main.jack", content, &options, &logger).unwrap();
+        let tree = parse_tokens("Main.jack", &tokenizer, &options, &logger).unwrap();
+        let (code, _) = write_vm("Main.jack", &tree, &options, &logger);
+
+        let mut sink = MemorySink::new();
+        write_outputs("Main.jack", &code, &options, &mut sink);
+
+        assert_eq!(sink.len(), 2);
+        assert_eq!(
+            sink.get("Main.vm").unwrap(),
+            "function Main.main 0\r\npush constant 0\r\nreturn"
+        );
+        assert_eq!(sink.get("Main.labels").unwrap(), "");
+        assert!(!std::path::Path::new("Main.vm").exists());
+    }
+
+    #[test]
+    fn read_source_with_invalid_utf8() {
+        let filename =
+            std::env::temp_dir().join(format!("jack_latin1_{}.jack", std::process::id()));
+        fs::write(&filename, b"class Caf\xe9 { }").unwrap();
+        let filename = filename.to_str().unwrap();
+
+        let result = read_source(filename);
+
+        assert_eq!(
+            result.err(),
+            Some(format!("file is not valid UTF-8: {}", filename))
+        );
+
+        fs::remove_file(filename).unwrap();
+    }
+
+    #[test]
+    fn compile_statement_with_declared_locals() {
+        let mut symbol_table = SymbolTable::new();
+        let mut writer = VmWriter::new();
+
+        let code = compile_statement("var int x, y;", &mut symbol_table, &mut writer).unwrap();
+        assert!(code.is_empty());
+
+        let code = compile_statement("let y = x + 1;", &mut symbol_table, &mut writer).unwrap();
+        assert_eq!(
+            code,
+            vec!["push local 0", "push constant 1", "add", "pop local 1"]
+        );
+
+        let result = compile_statement("x y", &mut symbol_table, &mut writer);
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "1:3: unexpected 'y' after expression"
+        );
+    }
+
+    #[test]
+    fn compile_statement_reports_errors() {
+        let mut symbol_table = SymbolTable::new();
+        let mut writer = VmWriter::new();
+        let mut compile = |source: &str| {
+            compile_statement(source, &mut symbol_table, &mut writer)
+                .err()
+                .unwrap()
+                .to_string()
+        };
+
+        assert_eq!(compile("let z = 1;"), "1:5: 'z' is not declared");
+        assert_eq!(compile("do f(a[0]);"), "1:6: 'a' is not declared");
+        assert_eq!(
+            compile("let x = 2foo;"),
+            "1:9: invalid identifier '2foo': identifiers must match [A-Za-z_][A-Za-z0-9_]*"
+        );
+        assert_eq!(compile("let x 1;"), "1:7: expected '=', found '1'");
+        assert_eq!(
+            compile("(1 + 2"),
+            "1:1: unbalanced parentheses: expected ')'"
+        );
+        assert_eq!(compile("var int x, x;"), "1:12: 'x' is already declared");
+    }
+
+    #[test]
+    fn quiet_logger_keeps_nothing() {
+        let logger = Logger::new(false);
+
+        logger.log("Main.jack", "tokenized: 1 tokens");
+
+        assert!(logger.get_messages().is_empty());
+    }
+}
@@ -1,25 +1,54 @@
+use crate::output::OutputSink;
 use crate::parser::*;
 use crate::tokenizer::{TokenType, Tokenizer};
-use std::fs;
 
-pub fn debug_tokenizer(filename: &str, tokenizer: &Tokenizer) {
+pub fn debug_tokenizer(filename: &str, tokenizer: &Tokenizer, sink: &mut dyn OutputSink) {
     let printable_tokens = print_tokens(tokenizer);
 
-    fs::write(
-        filename.replace(".jack", "T.xml"),
-        printable_tokens.join("\r\n"),
-    )
-    .expect("Something failed on write file to disk");
+    sink.write(
+        filename.replace(".jack", "T.xml").as_str(),
+        printable_tokens.join("\r\n").as_str(),
+    );
 }
 
-pub fn debug_parsed_tree(filename: &str, root: &TokenTreeItem) {
+pub fn debug_parsed_tree(filename: &str, root: &TokenTreeItem, sink: &mut dyn OutputSink) {
     let mut result: Vec<String> = Vec::new();
 
     result.extend(debug_token_item(root));
     result.push(String::new());
 
-    fs::write(filename.replace(".jack", ".xml"), result.join("\r\n"))
-        .expect("Something failed on write file to disk");
+    sink.write(
+        filename.replace(".jack", ".xml").as_str(),
+        result.join("\r\n").as_str(),
+    );
+}
+
+/// Writes the tokens and the parse tree of a class to one `C.xml` file, so
+/// both can be reviewed together.
+pub fn debug_combined(
+    filename: &str,
+    tokenizer: &Tokenizer,
+    root: &TokenTreeItem,
+    sink: &mut dyn OutputSink,
+) {
+    sink.write(
+        filename.replace(".jack", "C.xml").as_str(),
+        combined_xml(tokenizer, root).join("\r\n").as_str(),
+    );
+}
+
+pub fn combined_xml(tokenizer: &Tokenizer, root: &TokenTreeItem) -> Vec<String> {
+    let mut result: Vec<String> = Vec::new();
+
+    tokenizer.reset();
+
+    result.push(String::from("<!-- tokens -->"));
+    result.extend(print_tokens(tokenizer));
+    result.push(String::from("<!-- parse tree -->"));
+    result.extend(debug_token_item(root));
+    result.push(String::new());
+
+    result
 }
 
 fn debug_token_item(item: &TokenTreeItem) -> Vec<String> {
@@ -33,13 +62,13 @@ fn debug_token_item(item: &TokenTreeItem) -> Vec<String> {
         result.push(format!(
             "<{}> {} </{}>",
             enum_to_str(item.get_type()),
-            parse_symbol(&item.get_value().as_str()),
+            parse_symbol(item.get_value().as_str()),
             enum_to_str(item.get_type())
         ));
     }
 
     for node in item.get_nodes() {
-        result.extend(debug_token_item(&node));
+        result.extend(debug_token_item(node));
     }
 
     if let Some(name) = &item.get_name() {
@@ -91,22 +120,95 @@ fn print_tokens(tokenizer: &Tokenizer) -> Vec<String> {
     result
 }
 
+/// Escapes the XML special characters of a token value, including the ones
+/// inside string constants.
 fn parse_symbol(value: &str) -> String {
-    if value == ">" {
-        return String::from("&gt;");
+    let mut result = String::with_capacity(value.len());
+
+    for c in value.chars() {
+        match c {
+            '>' => result.push_str("&gt;"),
+            '<' => result.push_str("&lt;"),
+            '&' => result.push_str("&amp;"),
+            '"' => result.push_str("&quot;"),
+            c => result.push(c),
+        }
     }
 
-    if value == "<" {
-        return String::from("&lt;");
-    }
+    result
+}
 
-    if value == "&" {
-        return String::from("&amp;");
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::writer::VmWriter;
+
+    #[test]
+    fn escape_quotes_inside_string_constant() {
+        assert_eq!(parse_symbol("say \"hi\""), "say &quot;hi&quot;");
+        assert_eq!(parse_symbol("\""), "&quot;");
+
+        let tokenizer = Tokenizer::new("\"a < b & c\"");
+
+        assert_eq!(
+            print_tokens(&tokenizer).get(1).unwrap(),
+            "<stringConstant> a &lt; b &amp; c </stringConstant>"
+        );
     }
 
-    if value == "\"" {
-        return String::from("&quot;");
+    #[test]
+    fn print_tokens_before_parsing_keeps_code() {
+        let source = "class Main { function void main() { var int i; let i = 1 + 2; do Output.printInt(i); return; } }";
+
+        let tokenizer = Tokenizer::new(source);
+        print_tokens(&tokenizer);
+        assert!(!tokenizer.has_next());
+
+        let root = ClassNode::build(&tokenizer).unwrap();
+
+        assert_eq!(
+            VmWriter::new().build(&root),
+            vec![
+                "function Main.main 1",
+                "push constant 1",
+                "push constant 2",
+                "add",
+                "pop local 0",
+                "push local 0",
+                "call Output.printInt 1",
+                "pop temp 0",
+                "push constant 0",
+                "return",
+            ]
+        );
     }
 
-    String::from(value)
+    #[test]
+    fn combined_xml_has_tokens_and_tree() {
+        let tokenizer = Tokenizer::new("class Main { }");
+        let root = ClassNode::build(&tokenizer).unwrap();
+
+        let xml = combined_xml(&tokenizer, &root);
+
+        assert_eq!(
+            xml,
+            vec![
+                "<!-- tokens -->",
+                "<tokens>",
+                "<keyword> class </keyword>",
+                "<identifier> Main </identifier>",
+                "<symbol> { </symbol>",
+                "<symbol> } </symbol>",
+                "</tokens>",
+                "<!-- parse tree -->",
+                "<class>",
+                "<keyword> class </keyword>",
+                "<identifier> Main </identifier>",
+                "<symbol> { </symbol>",
+                "<symbol> } </symbol>",
+                "</class>",
+                "",
+            ]
+        );
+    }
 }
@@ -0,0 +1,106 @@
+use crate::error::CompileError;
+
+pub const DEFAULT_MAX_ERRORS: usize = 20;
+
+/// Collects the errors found while compiling a file, up to `max_errors`.
+#[derive(Debug)]
+pub struct Diagnostics {
+    errors: Vec<CompileError>,
+    max_errors: usize,
+}
+
+impl Diagnostics {
+    pub fn new(max_errors: usize) -> Diagnostics {
+        Diagnostics {
+            errors: Vec::new(),
+            max_errors,
+        }
+    }
+
+    pub fn push(&mut self, error: CompileError) {
+        if !self.is_full() {
+            self.errors.push(error);
+        }
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.errors.len() >= self.max_errors
+    }
+
+    pub fn has_errors(&self) -> bool {
+        !self.errors.is_empty()
+    }
+
+    pub fn get_errors(&self) -> &Vec<CompileError> {
+        &self.errors
+    }
+
+    pub fn render(&self, filename: &str) -> Vec<String> {
+        let mut result: Vec<String> = self
+            .errors
+            .iter()
+            .map(|error| format!("{}: error: {}", filename, error))
+            .collect();
+
+        if self.is_full() {
+            result.push(format!(
+                "{}: too many errors, stopped after {}",
+                filename, self.max_errors
+            ));
+        }
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tokenizer::Position;
+
+    #[test]
+    fn push_stops_at_limit() {
+        let mut diagnostics = Diagnostics::new(2);
+
+        for column in 1..6 {
+            diagnostics.push(CompileError::NoProgress {
+                position: Position::new(1, column),
+            });
+        }
+
+        assert_eq!(diagnostics.get_errors().len(), 2);
+        assert!(diagnostics.is_full());
+    }
+
+    #[test]
+    fn render_appends_too_many_errors_note() {
+        let mut diagnostics = Diagnostics::new(1);
+        diagnostics.push(CompileError::NoProgress {
+            position: Position::new(1, 3),
+        });
+
+        let lines = diagnostics.render("Main.jack");
+
+        assert_eq!(lines.len(), 2);
+        assert_eq!(
+            lines.get(1).unwrap(),
+            "Main.jack: too many errors, stopped after 1"
+        );
+    }
+
+    #[test]
+    fn render_without_limit_reached() {
+        let mut diagnostics = Diagnostics::new(20);
+        diagnostics.push(CompileError::NoProgress {
+            position: Position::new(1, 3),
+        });
+
+        let lines = diagnostics.render("Main.jack");
+
+        assert_eq!(lines.len(), 1);
+        assert_eq!(
+            lines.first().unwrap(),
+            "Main.jack: error: 1:3: parser made no progress, aborting to avoid an infinite loop"
+        );
+    }
+}
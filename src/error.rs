@@ -0,0 +1,37 @@
+use std::fmt;
+
+use crate::tokenizer::Position;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CompileError {
+    NoProgress { position: Position },
+    InvalidTokenCache { message: String },
+    Syntax { message: String, position: Position },
+}
+
+impl CompileError {
+    pub fn syntax(message: &str, position: Position) -> CompileError {
+        CompileError::Syntax {
+            message: String::from(message),
+            position,
+        }
+    }
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CompileError::NoProgress { position } => write!(
+                f,
+                "{}: parser made no progress, aborting to avoid an infinite loop",
+                position
+            ),
+            CompileError::InvalidTokenCache { message } => {
+                write!(f, "Invalid token cache: {}", message)
+            }
+            CompileError::Syntax { message, position } => write!(f, "{}: {}", position, message),
+        }
+    }
+}
+
+impl std::error::Error for CompileError {}
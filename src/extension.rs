@@ -0,0 +1,134 @@
+use std::rc::Rc;
+
+use crate::{error::CompileError, parser::TokenTreeItem, tokenizer::Tokenizer, writer::VmWriter};
+
+/// A custom statement recognized by the parser and lowered by the writer.
+///
+/// The statement starts with `keyword` and is stored on the tree under
+/// `name()` (by default `<keyword>Statement`, like the built-in statements).
+pub trait StatementExtension {
+    fn keyword(&self) -> &str;
+
+    fn name(&self) -> String {
+        format!("{}Statement", self.keyword())
+    }
+
+    fn build(&self, tokenizer: &Tokenizer) -> Result<TokenTreeItem, CompileError>;
+
+    fn write(&self, writer: &mut VmWriter, tree: &TokenTreeItem) -> Vec<String>;
+}
+
+#[derive(Clone, Default)]
+pub struct StatementRegistry {
+    extensions: Vec<Rc<dyn StatementExtension>>,
+}
+
+impl StatementRegistry {
+    pub fn new() -> StatementRegistry {
+        StatementRegistry {
+            extensions: Vec::new(),
+        }
+    }
+
+    pub fn register(&mut self, extension: Rc<dyn StatementExtension>) {
+        self.extensions.push(extension);
+    }
+
+    pub fn find_by_keyword(&self, keyword: &str) -> Option<Rc<dyn StatementExtension>> {
+        self.extensions
+            .iter()
+            .find(|extension| extension.keyword() == keyword)
+            .cloned()
+    }
+
+    pub fn find_by_name(&self, name: &str) -> Option<Rc<dyn StatementExtension>> {
+        self.extensions
+            .iter()
+            .find(|extension| extension.name() == name)
+            .cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{ClassNode, Expression};
+
+    struct PrintStatement {}
+
+    impl StatementExtension for PrintStatement {
+        fn keyword(&self) -> &str {
+            "print"
+        }
+
+        fn build(&self, tokenizer: &Tokenizer) -> Result<TokenTreeItem, CompileError> {
+            let mut root = TokenTreeItem::new_root("printStatement");
+
+            root.push(tokenizer.consume("print")?);
+            root.push_item(Expression::build(tokenizer)?);
+            root.push(tokenizer.consume(";")?);
+
+            Ok(root)
+        }
+
+        fn write(&self, writer: &mut VmWriter, tree: &TokenTreeItem) -> Vec<String> {
+            let mut result = Vec::new();
+
+            result.extend(writer.build(tree.get_nodes().get(1).unwrap()));
+            result.push(String::from("call Output.printInt 1"));
+            result.push(String::from("pop temp 0"));
+
+            result
+        }
+    }
+
+    fn build_registry() -> StatementRegistry {
+        let mut registry = StatementRegistry::new();
+        registry.register(Rc::new(PrintStatement {}));
+
+        registry
+    }
+
+    #[test]
+    fn find_registered_extension() {
+        let registry = build_registry();
+
+        assert!(registry.find_by_keyword("print").is_some());
+        assert!(registry.find_by_name("printStatement").is_some());
+        assert!(registry.find_by_keyword("let").is_none());
+    }
+
+    #[test]
+    fn build_custom_statement() {
+        let source = "class Main { function void main() { print 1 + 2; return; } }";
+        let mut tokenizer = Tokenizer::new(source);
+        tokenizer.set_statement_registry(build_registry());
+        let tree = ClassNode::build(&tokenizer).unwrap();
+
+        let mut writer = VmWriter::new();
+        writer.set_statement_registry(build_registry());
+        let code: Vec<String> = writer.build(&tree);
+
+        assert_eq!(code.first().unwrap(), "function Main.main 0");
+        assert_eq!(code.get(1).unwrap(), "push constant 1");
+        assert_eq!(code.get(2).unwrap(), "push constant 2");
+        assert_eq!(code.get(3).unwrap(), "add");
+        assert_eq!(code.get(4).unwrap(), "call Output.printInt 1");
+        assert_eq!(code.get(5).unwrap(), "pop temp 0");
+        assert_eq!(code.get(6).unwrap(), "push constant 0");
+        assert_eq!(code.get(7).unwrap(), "return");
+    }
+
+    #[test]
+    fn build_custom_statement_without_registry() {
+        let source = "class Main { function void main() { print 1; return; } }";
+        let tokenizer = Tokenizer::new(source);
+
+        let error = ClassNode::build(&tokenizer).err().unwrap();
+
+        assert_eq!(
+            error.to_string(),
+            "1:37: expected a statement, found 'print'"
+        );
+    }
+}
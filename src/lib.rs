@@ -0,0 +1,23 @@
+pub mod builder;
+pub mod cache;
+pub mod color;
+pub mod compiler;
+pub mod debug;
+pub mod diagnostics;
+pub mod error;
+pub mod extension;
+pub mod lint;
+pub mod optimizer;
+pub mod options;
+pub mod output;
+pub mod parser;
+pub mod references;
+pub mod repl;
+pub mod scan;
+pub mod signature;
+pub mod summary;
+pub mod symbols;
+pub mod timing;
+pub mod tokenizer;
+pub mod vm;
+pub mod writer;
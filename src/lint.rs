@@ -0,0 +1,557 @@
+use std::collections::HashSet;
+use std::fmt;
+
+use crate::{
+    parser::TokenTreeItem,
+    tokenizer::{Position, TokenItem, TokenType},
+};
+
+/// A suspicious construct that still compiles.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Warning {
+    message: String,
+    position: Position,
+}
+
+impl Warning {
+    pub fn new(message: &str, position: Position) -> Warning {
+        Warning {
+            message: String::from(message),
+            position,
+        }
+    }
+
+    pub fn get_message(&self) -> &String {
+        &self.message
+    }
+
+    pub fn get_position(&self) -> Position {
+        self.position
+    }
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.position, self.message)
+    }
+}
+
+/// Runs every lint over a parsed class.
+pub fn lint(tree: &TokenTreeItem) -> Vec<Warning> {
+    let mut result = Vec::new();
+
+    check_subroutine_names(tree, &mut result);
+    visit(tree, &mut result);
+
+    result
+}
+
+/// Checks the Jack naming convention: classes start with an uppercase letter
+/// and variables with a lowercase one.
+pub fn lint_naming(tree: &TokenTreeItem) -> Vec<Warning> {
+    let mut result = Vec::new();
+
+    if let Some(name) = tree
+        .get_nodes()
+        .get(1)
+        .and_then(|node| node.get_item().as_ref())
+    {
+        if !name
+            .get_value()
+            .starts_with(|c: char| c.is_ascii_uppercase())
+        {
+            result.push(Warning::new(
+                format!(
+                    "class name '{}' should start with an uppercase letter",
+                    name.get_value()
+                )
+                .as_str(),
+                name.get_position(),
+            ));
+        }
+    }
+
+    visit_variable_names(tree, &mut result);
+
+    result
+}
+
+fn visit_variable_names(tree: &TokenTreeItem, warnings: &mut Vec<Warning>) {
+    let names: Vec<&TokenTreeItem> = match tree.get_name().as_deref() {
+        Some("classVarDec") | Some("varDec") => {
+            tree.get_nodes().iter().skip(2).step_by(2).collect()
+        }
+        Some("parameterList") => tree.get_nodes().iter().skip(1).step_by(3).collect(),
+        _ => Vec::new(),
+    };
+
+    for item in names.iter().filter_map(|node| node.get_item().as_ref()) {
+        if item
+            .get_value()
+            .starts_with(|c: char| c.is_ascii_uppercase())
+        {
+            warnings.push(Warning::new(
+                format!(
+                    "variable '{}' should start with a lowercase letter",
+                    item.get_value()
+                )
+                .as_str(),
+                item.get_position(),
+            ));
+        }
+    }
+
+    for node in tree.get_nodes() {
+        visit_variable_names(node, warnings);
+    }
+}
+
+fn visit(tree: &TokenTreeItem, warnings: &mut Vec<Warning>) {
+    match tree.get_name().as_deref() {
+        Some("ifStatement") | Some("whileStatement") => {
+            check_constant_condition(tree, warnings);
+            check_empty_blocks(tree, warnings);
+        }
+        Some("expression") => check_chained_comparison(tree, warnings),
+        Some("subroutineBody") => check_use_before_assign(tree, warnings),
+        Some("term") => check_leading_zeros(tree, warnings),
+        _ => (),
+    }
+
+    for node in tree.get_nodes() {
+        visit(node, warnings);
+    }
+}
+
+fn check_constant_condition(tree: &TokenTreeItem, warnings: &mut Vec<Warning>) {
+    let value = match tree.get_nodes().get(2).and_then(boolean_literal) {
+        Some(value) => value,
+        None => return,
+    };
+
+    let keyword = tree
+        .get_nodes()
+        .first()
+        .unwrap()
+        .get_item()
+        .as_ref()
+        .unwrap();
+
+    // the `else` keyword follows the closing brace of the if body
+    let has_else = tree.get_nodes().len() > 7;
+
+    let message = match (keyword.get_value().as_str(), value) {
+        ("while", true) => "always true loop never exits",
+        ("while", false) => "always false loop body is dead",
+        (_, true) if has_else => "always true condition makes the else branch dead",
+        (_, true) => "always true condition",
+        (_, false) => "always false if body is dead",
+    };
+
+    warnings.push(Warning::new(message, keyword.get_position()));
+}
+
+/// An empty `{}` after `if`, `else` or `while` is most likely unfinished code.
+fn check_empty_blocks(tree: &TokenTreeItem, warnings: &mut Vec<Warning>) {
+    for (keyword, statements) in [(0, 5), (7, 9)] {
+        let keyword = tree
+            .get_nodes()
+            .get(keyword)
+            .and_then(|node| node.get_item().as_ref());
+
+        if let (Some(keyword), Some(statements)) = (keyword, tree.get_nodes().get(statements)) {
+            if statements.get_nodes().is_empty() {
+                warnings.push(Warning::new(
+                    format!("empty {} body", keyword.get_value()).as_str(),
+                    keyword.get_position(),
+                ));
+            }
+        }
+    }
+}
+
+/// Jack has no precedence, so `a < b < c` is `(a < b) < c`: the second
+/// comparison gets a boolean on its left.
+fn check_chained_comparison(tree: &TokenTreeItem, warnings: &mut Vec<Warning>) {
+    let comparison = |index: usize| {
+        tree.get_nodes()
+            .get(index)
+            .and_then(|node| node.get_item().as_ref())
+            .filter(|item| ["<", ">", "="].contains(&item.get_value().as_str()))
+    };
+
+    let mut i = 3;
+
+    while i < tree.get_nodes().len() {
+        if let (Some(previous), Some(op)) = (comparison(i - 2), comparison(i)) {
+            warnings.push(Warning::new(
+                format!(
+                    "chained comparison: the left side of '{}' is the boolean result of '{}'",
+                    op.get_value(),
+                    previous.get_value()
+                )
+                .as_str(),
+                op.get_position(),
+            ));
+        }
+
+        i += 2;
+    }
+}
+
+/// `007` compiles to `7`; Jack has no octal, so the zeros are most likely a
+/// typo.
+fn check_leading_zeros(tree: &TokenTreeItem, warnings: &mut Vec<Warning>) {
+    if let Some(item) = tree
+        .get_nodes()
+        .first()
+        .and_then(|node| node.get_item().as_ref())
+    {
+        let value = item.get_value();
+
+        if item.get_type() == TokenType::Integer && value.len() > 1 && value.starts_with('0') {
+            warnings.push(Warning::new(
+                format!(
+                    "integer '{}' has leading zeros and is read as {}",
+                    value,
+                    value.parse::<i16>().unwrap()
+                )
+                .as_str(),
+                item.get_position(),
+            ));
+        }
+    }
+}
+
+/// Locals start with whatever the stack held before, so reading one before
+/// any `let` gives garbage. The check follows statements in source order and
+/// treats a `let` anywhere, even inside a branch, as an assignment, so it only
+/// flags the obvious cases.
+fn check_use_before_assign(body: &TokenTreeItem, warnings: &mut Vec<Warning>) {
+    let mut locals = HashSet::new();
+
+    for var_dec in body.get_nodes() {
+        if var_dec.get_name().as_deref() != Some("varDec") {
+            continue;
+        }
+
+        for name in var_dec.get_nodes().iter().skip(2).step_by(2) {
+            if let Some(item) = name.get_item() {
+                locals.insert(item.get_value());
+            }
+        }
+    }
+
+    let mut state = Assignments {
+        locals,
+        assigned: HashSet::new(),
+        reported: HashSet::new(),
+    };
+
+    for statements in body.get_nodes() {
+        if statements.get_name().as_deref() == Some("statements") {
+            state.visit_nodes(statements.get_nodes(), warnings);
+        }
+    }
+}
+
+struct Assignments {
+    locals: HashSet<String>,
+    assigned: HashSet<String>,
+    reported: HashSet<String>,
+}
+
+impl Assignments {
+    fn visit_nodes(&mut self, nodes: &[TokenTreeItem], warnings: &mut Vec<Warning>) {
+        for (i, node) in nodes.iter().enumerate() {
+            match (node.get_name().as_deref(), node.get_item()) {
+                (Some("letStatement"), _) => self.visit_let(node, warnings),
+                (Some(_), _) => self.visit_nodes(node.get_nodes(), warnings),
+                (None, Some(item)) => {
+                    let value = |index: Option<usize>| {
+                        index
+                            .and_then(|index| nodes.get(index))
+                            .and_then(|node| node.get_item().as_ref())
+                            .map(|item| item.get_value())
+                    };
+
+                    let is_member = value(i.checked_sub(1)).as_deref() == Some(".");
+                    let is_call = value(Some(i + 1)).as_deref() == Some("(");
+
+                    if !is_member && !is_call {
+                        self.read(item, warnings);
+                    }
+                }
+                (None, None) => (),
+            }
+        }
+    }
+
+    /// `let x = ...` assigns `x` after its value is read; `let a[i] = ...`
+    /// reads `a` as well.
+    fn visit_let(&mut self, tree: &TokenTreeItem, warnings: &mut Vec<Warning>) {
+        let target = tree
+            .get_nodes()
+            .get(1)
+            .unwrap()
+            .get_item()
+            .as_ref()
+            .unwrap();
+        let rest = &tree.get_nodes()[2..];
+
+        if rest
+            .first()
+            .and_then(|node| node.get_item().as_ref())
+            .is_some_and(|item| item.get_value() == "[")
+        {
+            self.read(target, warnings);
+        }
+
+        self.visit_nodes(rest, warnings);
+        self.assigned.insert(target.get_value());
+    }
+
+    fn read(&mut self, item: &TokenItem, warnings: &mut Vec<Warning>) {
+        let name = item.get_value();
+
+        if item.get_type() != TokenType::Identifier
+            || !self.locals.contains(&name)
+            || self.assigned.contains(&name)
+            || self.reported.contains(&name)
+        {
+            return;
+        }
+
+        warnings.push(Warning::new(
+            format!("local '{}' is used before it is assigned", name).as_str(),
+            item.get_position(),
+        ));
+        self.reported.insert(name);
+    }
+}
+
+/// A function or method named like its class compiles to `function Foo.Foo`,
+/// which reads like a constructor but is not one.
+fn check_subroutine_names(tree: &TokenTreeItem, warnings: &mut Vec<Warning>) {
+    let class_name = match tree
+        .get_nodes()
+        .get(1)
+        .and_then(|node| node.get_item().as_ref())
+    {
+        Some(item) => item.get_value(),
+        None => return,
+    };
+
+    for subroutine in tree.get_nodes() {
+        if subroutine.get_name().as_deref() != Some("subroutineDec") {
+            continue;
+        }
+
+        let item = |index: usize| subroutine.get_nodes().get(index)?.get_item().as_ref();
+
+        if let (Some(kind), Some(name)) = (item(0), item(2)) {
+            if kind.get_value() != "constructor" && name.get_value() == class_name {
+                warnings.push(Warning::new(
+                    format!(
+                        "{} '{}' has the same name as its class but is not a constructor",
+                        kind.get_value(),
+                        name.get_value()
+                    )
+                    .as_str(),
+                    name.get_position(),
+                ));
+            }
+        }
+    }
+}
+
+/// The value of an expression made of a single `true` or `false`.
+fn boolean_literal(expression: &TokenTreeItem) -> Option<bool> {
+    if expression.get_nodes().len() != 1 {
+        return None;
+    }
+
+    let term = expression.get_nodes().first()?;
+    if term.get_nodes().len() != 1 {
+        return None;
+    }
+
+    let item = term.get_nodes().first()?.get_item().as_ref()?;
+
+    match (item.get_type(), item.get_value().as_str()) {
+        (TokenType::Keyword, "true") => Some(true),
+        (TokenType::Keyword, "false") => Some(false),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parser::ClassNode, tokenizer::Tokenizer};
+
+    fn lint_source(source: &str) -> Vec<String> {
+        let tokenizer = Tokenizer::new(source);
+        let tree = ClassNode::build(&tokenizer).unwrap();
+
+        lint(&tree)
+            .iter()
+            .map(|warning| warning.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn lint_if_true() {
+        let warnings = lint_source(
+            "class Main { function void main() { if (true) { do Main.run(); } return; } }",
+        );
+
+        assert_eq!(warnings, vec!["1:37: always true condition"]);
+    }
+
+    #[test]
+    fn lint_if_true_with_else() {
+        let warnings = lint_source(
+            "class Main { function void main() { if (true) { do Main.run(); } else { do Main.stop(); } return; } }",
+        );
+
+        assert_eq!(
+            warnings,
+            vec!["1:37: always true condition makes the else branch dead"]
+        );
+    }
+
+    #[test]
+    fn lint_while_false() {
+        let warnings = lint_source(
+            "class Main { function void main() { while (false) { do Main.run(); } return; } }",
+        );
+
+        assert_eq!(warnings, vec!["1:37: always false loop body is dead"]);
+    }
+
+    #[test]
+    fn lint_skips_non_literal_conditions() {
+        let warnings = lint_source(
+            "class Main { function void main(boolean a) { while (a) { let a = ~true; } if (~false) { return; } return; } }",
+        );
+
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn lint_empty_if_and_else() {
+        let warnings = lint_source(
+            "class Main { function void main(boolean c) { if (c) {} else {} if (c) { return; } else { return; } return; } }",
+        );
+
+        assert_eq!(
+            warnings,
+            vec!["1:46: empty if body", "1:56: empty else body"]
+        );
+    }
+
+    #[test]
+    fn lint_empty_while() {
+        let warnings =
+            lint_source("class Main { function void main(boolean c) { while (c) {} return; } }");
+
+        assert_eq!(warnings, vec!["1:46: empty while body"]);
+    }
+
+    #[test]
+    fn lint_subroutine_named_like_class() {
+        let warnings = lint_source("class Foo { function void Foo() { return; } }");
+
+        assert_eq!(
+            warnings,
+            vec!["1:27: function 'Foo' has the same name as its class but is not a constructor"]
+        );
+    }
+
+    #[test]
+    fn lint_constructor_named_like_class() {
+        let warnings = lint_source("class Foo { constructor Foo Foo() { return this; } }");
+
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn lint_chained_comparison() {
+        let warnings = lint_source(
+            "class Main { function void main(int a, int b, int c) { if (a < b < c) { return; } if ((a < b) & (b < c)) { return; } return; } }",
+        );
+
+        assert_eq!(
+            warnings,
+            vec!["1:66: chained comparison: the left side of '<' is the boolean result of '<'"]
+        );
+    }
+
+    #[test]
+    fn lint_naming_lowercase_class() {
+        let tokenizer = Tokenizer::new("class foo { function void main() { return; } }");
+        let tree = ClassNode::build(&tokenizer).unwrap();
+
+        assert_eq!(
+            lint_naming(&tree)
+                .iter()
+                .map(|warning| warning.to_string())
+                .collect::<Vec<String>>(),
+            vec!["1:7: class name 'foo' should start with an uppercase letter"]
+        );
+    }
+
+    #[test]
+    fn lint_naming_uppercase_variables() {
+        let tokenizer = Tokenizer::new(
+            "class Main { field int Size, count; function void main(int a, int B) { var int MyVar, other; let MyVar = B; return; } }",
+        );
+        let tree = ClassNode::build(&tokenizer).unwrap();
+
+        assert_eq!(
+            lint_naming(&tree)
+                .iter()
+                .map(|warning| warning.to_string())
+                .collect::<Vec<String>>(),
+            vec![
+                "1:24: variable 'Size' should start with a lowercase letter",
+                "1:67: variable 'B' should start with a lowercase letter",
+                "1:80: variable 'MyVar' should start with a lowercase letter",
+            ]
+        );
+    }
+
+    #[test]
+    fn lint_leading_zeros() {
+        let warnings = lint_source(
+            "class Main { function void main() { var int x; let x = 007; let x = 0; return; } }",
+        );
+
+        assert_eq!(
+            warnings,
+            vec!["1:56: integer '007' has leading zeros and is read as 7"]
+        );
+    }
+
+    #[test]
+    fn lint_use_before_assign() {
+        let warnings = lint_source(
+            "class Main { function void main() { var int x, y; let y = x + 1; let x = y; do Output.printInt(x); return; } }",
+        );
+
+        assert_eq!(
+            warnings,
+            vec!["1:59: local 'x' is used before it is assigned"]
+        );
+    }
+
+    #[test]
+    fn lint_assigned_locals() {
+        let warnings = lint_source(
+            "class Main { function void main() { var Array a; var Point p; var int i; let a = Array.new(3); let i = 0; let a[i] = i; let p = Point.new(); do p.draw(a[i]); return; } }",
+        );
+
+        assert!(warnings.is_empty());
+    }
+}
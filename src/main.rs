@@ -1,61 +1,369 @@
 use std::fs;
-use std::{env, path::Path};
+use std::io::Read;
+use std::{env, io, path::Path, process};
 
-mod builder;
-mod debug;
-mod parser;
-mod tokenizer;
-mod writer;
-
-use crate::builder::build_content;
-use crate::debug::{debug_parsed_tree, debug_tokenizer};
-use crate::parser::ClassNode;
-use crate::tokenizer::Tokenizer;
-use crate::writer::VmWriter;
+use jack_compiler::builder::lint_indentation;
+use jack_compiler::cache::{deserialize_tokens, serialize_tokens, CacheSettings};
+use jack_compiler::color::{colorize, use_color};
+use jack_compiler::compiler::{
+    parse_tokens, read_source, tokenize_source, write_outputs, write_vm, Logger,
+};
+use jack_compiler::debug::{debug_combined, debug_parsed_tree, debug_tokenizer};
+use jack_compiler::error::CompileError;
+use jack_compiler::lint::{lint, lint_naming, Warning};
+use jack_compiler::options::Options;
+use jack_compiler::output::{FileSink, OutputSink};
+use jack_compiler::parser::TokenTreeItem;
+use jack_compiler::references::unresolved_classes;
+use jack_compiler::repl::run_repl;
+use jack_compiler::scan::find_jack_files;
+use jack_compiler::summary::ClassSummary;
+use jack_compiler::symbols::write_symbols_csv;
+use jack_compiler::timing::{Phase, PhaseTimes};
+use jack_compiler::tokenizer::Tokenizer;
+use jack_compiler::vm::{bootstrap_program, opcode_report, opcode_stats};
 
 fn main() {
     let args: Vec<String> = env::args().collect();
-    let path = args.get(1).expect("Please supply a folder or file name");
+    let options = Options::parse(&args).unwrap_or_else(|message| {
+        eprintln!("{}", message);
+        process::exit(1);
+    });
+
+    if options.repl {
+        let stdin = io::stdin();
+        run_repl(stdin.lock(), io::stdout(), &options).expect("Something failed on the REPL");
+        return;
+    }
+
+    let logger = Logger::new(options.verbose);
+    let times = PhaseTimes::new();
+    let mut sink = FileSink::new();
+
+    let path = options.path.as_str();
+    let mut warnings = 0;
+    let mut failed = false;
+    let mut generated = Vec::new();
+    let mut roots = Vec::new();
+    let mut summaries = Vec::new();
+
+    if path == "-" {
+        let (stdin_warnings, code) = compile_stdin(&options, &logger);
+        warnings += stdin_warnings;
+        generated.extend(code);
+    } else if path.ends_with(".jack") {
+        match parse_file(path, &options, &logger, &times, &mut sink) {
+            Some((file_warnings, code, root)) => {
+                warnings += file_warnings.len();
+                summaries.push(ClassSummary::new(&root, &code, &file_warnings));
+                generated.extend(code);
+                roots.push(root);
+            }
+            None => failed = true,
+        }
+    } else if fs::metadata(path).is_ok_and(|metadata| metadata.is_file()) {
+        report(
+            &options,
+            format!("{}: error: not a .jack file or a folder", path),
+        );
+        process::exit(1);
+    } else {
+        let scan = find_jack_files(Path::new(path), options.max_depth);
+
+        for error in scan.get_errors() {
+            report(&options, format!("warning: skipped {}", error));
+        }
+
+        let mut classes = Vec::new();
+
+        for file in scan.get_files() {
+            let filename = match file.to_str() {
+                Some(filename) => filename,
+                None => {
+                    report(
+                        &options,
+                        format!(
+                            "warning: skipped {}: file name is not valid UTF-8",
+                            file.display()
+                        ),
+                    );
+                    continue;
+                }
+            };
+
+            match parse_file(filename, &options, &logger, &times, &mut sink) {
+                Some((file_warnings, code, root)) => {
+                    warnings += file_warnings.len();
+                    summaries.push(ClassSummary::new(&root, &code, &file_warnings));
+                    generated.extend(code.iter().cloned());
+                    classes.push(code);
+                    roots.push(root);
+                }
+                None => failed = true,
+            }
+        }
+
+        if let Some(bootstrap) = &options.bootstrap {
+            sink.write(
+                bootstrap,
+                bootstrap_program(&options.entry, &classes)
+                    .join("\r\n")
+                    .as_str(),
+            );
+        }
+    }
+
+    if options.time {
+        for line in times.report() {
+            eprintln!("{}", line);
+        }
+    }
+
+    if options.unresolved {
+        for class_name in unresolved_classes(&roots) {
+            report(
+                &options,
+                format!("warning: class '{}' is used but never defined", class_name),
+            );
+            warnings += 1;
+        }
+    }
 
-    let debug = args.get(2).is_some();
+    if options.summary_json {
+        for summary in summaries.iter() {
+            println!("{}", summary.to_json());
+        }
+    }
+
+    if options.opcode_stats {
+        for line in opcode_report(&opcode_stats(&generated)) {
+            eprintln!("{}", line);
+        }
+    }
+
+    if failed {
+        process::exit(1);
+    }
 
-    if path.ends_with(".jack") {
-        parse_file(&path, &debug);
+    if warnings > 0 && options.warnings_as_errors {
+        report(
+            &options,
+            format!("error: {} warning(s) treated as errors", warnings),
+        );
+        process::exit(1);
+    }
+}
+
+/// Compiles one file and returns its warnings along with its VM code and
+/// tree. Generated files go to `sink`. A file that cannot be read or has
+/// errors is reported and skipped, returning `None`.
+fn parse_file(
+    filename: &str,
+    options: &Options,
+    logger: &Logger,
+    times: &PhaseTimes,
+    sink: &mut dyn OutputSink,
+) -> Option<(Vec<Warning>, Vec<String>, TokenTreeItem)> {
+    let content = match read_source(filename) {
+        Ok(content) => content,
+        Err(message) => {
+            report(options, format!("{}: error: {}", filename, message));
+            return None;
+        }
+    };
+
+    let indentation_warnings = if options.lint_whitespace {
+        lint_indentation(&content)
     } else {
-        let file_list = fs::read_dir(path).unwrap();
+        Vec::new()
+    };
 
-        for file in file_list {
-            let file_path_buff = file.unwrap().path();
-            let file_path = file_path_buff.to_str().unwrap();
-            let file_name = Path::new(file_path).file_name().unwrap().to_str().unwrap();
+    let tokenizer = match times.time(Phase::Tokenize, || {
+        tokenize_file(filename, content, options, logger, sink)
+    }) {
+        Ok(tokenizer) => tokenizer,
+        Err(error) => {
+            report(options, format!("{}: error: {}", filename, error));
+            return None;
+        }
+    };
+
+    if options.debug {
+        debug_tokenizer(filename, &tokenizer, sink);
+    }
 
-            if file_name.ends_with(".jack") {
-                parse_file(&file_path, &debug);
+    let root = match times.time(Phase::Parse, || {
+        parse_tokens(filename, &tokenizer, options, logger)
+    }) {
+        Ok(root) => root,
+        Err(diagnostics) => {
+            for line in diagnostics.render(filename) {
+                report(options, line);
             }
+            return None;
+        }
+    };
+
+    if options.debug {
+        debug_parsed_tree(filename, &root, sink);
+    }
+
+    if options.combined_xml {
+        debug_combined(filename, &tokenizer, &root, sink);
+    }
+
+    if options.symbols_csv {
+        write_symbols_csv(filename, &root, sink);
+    }
+
+    let mut warnings = if options.lint {
+        lint(&root)
+    } else {
+        Vec::new()
+    };
+
+    warnings.extend(indentation_warnings);
+
+    if options.lint_naming {
+        warnings.extend(lint_naming(&root));
+    }
+
+    let (code, writer_warnings) =
+        times.time(Phase::Write, || write_vm(filename, &root, options, logger));
+    warnings.extend(writer_warnings);
+
+    for warning in warnings.iter() {
+        report(options, format!("{}: warning: {}", filename, warning));
+    }
+
+    write_outputs(filename, &code, options, sink);
+
+    Some((warnings, code, root))
+}
+
+/// Compiles a class read from stdin to stdout, attributing diagnostics to
+/// `--stdin-name`, and returns how many warnings it had along with its VM code.
+fn compile_stdin(options: &Options, logger: &Logger) -> (usize, Vec<String>) {
+    let name = options.stdin_name.as_str();
+    let mut content = String::new();
+
+    if io::stdin().read_to_string(&mut content).is_err() {
+        report(
+            options,
+            format!("{}: error: input is not valid UTF-8", name),
+        );
+        process::exit(1);
+    }
+
+    let indentation_warnings = if options.lint_whitespace {
+        lint_indentation(&content)
+    } else {
+        Vec::new()
+    };
+
+    let tokenizer = tokenize_source(name, content, options, logger).unwrap_or_else(|error| {
+        report(options, format!("{}: error: {}", name, error));
+        process::exit(1);
+    });
+
+    let root = parse_tokens(name, &tokenizer, options, logger).unwrap_or_else(|diagnostics| {
+        for line in diagnostics.render(name) {
+            report(options, line);
         }
+        process::exit(1);
+    });
+
+    let mut warnings = if options.lint {
+        lint(&root)
+    } else {
+        Vec::new()
+    };
+
+    warnings.extend(indentation_warnings);
+
+    if options.lint_naming {
+        warnings.extend(lint_naming(&root));
+    }
+
+    let (code, writer_warnings) = write_vm(name, &root, options, logger);
+    warnings.extend(writer_warnings);
+
+    for warning in warnings.iter() {
+        report(options, format!("{}: warning: {}", name, warning));
+    }
+
+    for line in code.iter() {
+        println!("{}", line);
+    }
+
+    (warnings.len(), code)
+}
+
+/// Prints a diagnostic line to stderr, colored unless disabled.
+fn report(options: &Options, line: String) {
+    if use_color(options.color) {
+        eprintln!("{}", colorize(&line));
+    } else {
+        eprintln!("{}", line);
     }
 }
 
-fn parse_file(filename: &str, debug: &bool) {
-    let content = fs::read_to_string(filename).expect("Something went wrong reading the file");
+/// Tokenizes `content`, the source of `filename`, or loads its tokens from
+/// the `--token-cache` file when that is newer than the source and was
+/// written with the same tokenizer settings.
+fn tokenize_file(
+    filename: &str,
+    content: String,
+    options: &Options,
+    logger: &Logger,
+    sink: &mut dyn OutputSink,
+) -> Result<Tokenizer, CompileError> {
+    let use_cache = options.token_cache;
+    let cache_name = filename.replace(".jack", ".tokens");
+    let settings = CacheSettings {
+        tab_width: options.tab_width,
+        limits: options.limits,
+    };
 
-    let clean_code = build_content(content);
+    if use_cache && is_cache_fresh(filename, &cache_name) {
+        let cached = fs::read(&cache_name).map(|bytes| deserialize_tokens(&bytes, settings));
 
-    let tokenizer = Tokenizer::new(&clean_code);
+        match cached {
+            Ok(Ok(tokenizer)) => {
+                logger.log(
+                    filename,
+                    format!(
+                        "loaded {} tokens from {}",
+                        tokenizer.get_tokens().len(),
+                        cache_name
+                    )
+                    .as_str(),
+                );
 
-    if *debug {
-        debug_tokenizer(filename, &tokenizer);
+                return Ok(tokenizer);
+            }
+            Ok(Err(error)) => logger.log(
+                filename,
+                format!("ignoring {}: {}", cache_name, error).as_str(),
+            ),
+            Err(_) => (),
+        }
     }
 
-    let root = ClassNode::build(&tokenizer);
+    let tokenizer = tokenize_source(filename, content, options, logger)?;
 
-    if *debug {
-        debug_parsed_tree(&filename, &root);
+    if use_cache {
+        sink.write_bytes(&cache_name, &serialize_tokens(&tokenizer, settings));
     }
 
-    let mut writer = VmWriter::new();
-    let code: Vec<String> = writer.build(&root);
+    Ok(tokenizer)
+}
 
-    fs::write(filename.replace(".jack", ".vm"), code.join("\r\n"))
-        .expect("Something failed on write file to disk");
+fn is_cache_fresh(filename: &str, cache_name: &str) -> bool {
+    let modified = |name: &str| fs::metadata(name).and_then(|metadata| metadata.modified());
+
+    match (modified(filename), modified(cache_name)) {
+        (Ok(source), Ok(cache)) => cache >= source,
+        _ => false,
+    }
 }
@@ -0,0 +1,172 @@
+use crate::{parser::TokenTreeItem, tokenizer::TokenType};
+
+/// A value known at compile time.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Constant {
+    Boolean(bool),
+    Integer(i16),
+}
+
+/// Evaluates an `expression` tree whose terms are all literals. Jack has no
+/// operator precedence, so the fold runs strictly left to right like the
+/// generated code does.
+///
+/// Integer folding follows the Hack runtime: values are 16-bit and wrap on
+/// overflow, `~` is a bitwise not, and `/` truncates toward zero like
+/// `Math.divide` (so `(-7) / 2` is `-3`, not `-4`). Rust's `i16` division
+/// truncates toward zero as well. A division by zero is left to the runtime.
+pub fn fold_expression(tree: &TokenTreeItem) -> Option<Constant> {
+    let nodes = tree.get_nodes();
+    let mut result = fold_term(nodes.first()?)?;
+
+    let mut i = 1;
+
+    while i < nodes.len() {
+        let op = nodes.get(i)?.get_item().as_ref()?.get_value();
+        let another_term = fold_term(nodes.get(i + 1)?)?;
+
+        result = fold_op(op.as_str(), result, another_term)?;
+
+        i += 2;
+    }
+
+    Some(result)
+}
+
+fn fold_term(tree: &TokenTreeItem) -> Option<Constant> {
+    let item = tree.get_nodes().first()?.get_item().as_ref()?;
+
+    match (item.get_type(), item.get_value().as_str()) {
+        (TokenType::Keyword, "true") => Some(Constant::Boolean(true)),
+        (TokenType::Keyword, "false") => Some(Constant::Boolean(false)),
+        (TokenType::Integer, value) => value.parse::<i16>().ok().map(Constant::Integer),
+        (TokenType::Symbol, "(") => fold_expression(tree.get_nodes().get(1)?),
+        (TokenType::Symbol, "~") => match fold_term(tree.get_nodes().get(1)?)? {
+            Constant::Boolean(value) => Some(Constant::Boolean(!value)),
+            Constant::Integer(value) => Some(Constant::Integer(!value)),
+        },
+        (TokenType::Symbol, "-") => match fold_term(tree.get_nodes().get(1)?)? {
+            Constant::Integer(value) => Some(Constant::Integer(value.wrapping_neg())),
+            Constant::Boolean(_) => None,
+        },
+        _ => None,
+    }
+}
+
+fn fold_op(op: &str, left: Constant, right: Constant) -> Option<Constant> {
+    match (op, left, right) {
+        ("&", Constant::Boolean(left), Constant::Boolean(right)) => {
+            Some(Constant::Boolean(left && right))
+        }
+        ("|", Constant::Boolean(left), Constant::Boolean(right)) => {
+            Some(Constant::Boolean(left || right))
+        }
+        (op, Constant::Integer(left), Constant::Integer(right)) => fold_integer_op(op, left, right),
+        _ => None,
+    }
+}
+
+fn fold_integer_op(op: &str, left: i16, right: i16) -> Option<Constant> {
+    let result = match op {
+        "+" => Constant::Integer(left.wrapping_add(right)),
+        "-" => Constant::Integer(left.wrapping_sub(right)),
+        "*" => Constant::Integer(left.wrapping_mul(right)),
+        "/" if right != 0 => Constant::Integer(left.wrapping_div(right)),
+        "&" => Constant::Integer(left & right),
+        "|" => Constant::Integer(left | right),
+        "<" => Constant::Boolean(left < right),
+        ">" => Constant::Boolean(left > right),
+        "=" => Constant::Boolean(left == right),
+        _ => return None,
+    };
+
+    Some(result)
+}
+
+/// Returns `n` when `tree` is the integer literal `2^n` (with `n > 0`),
+/// so a multiplication or division by it can become a shift.
+pub fn shift_amount(tree: &TokenTreeItem) -> Option<u32> {
+    if tree.get_nodes().len() != 1 {
+        return None;
+    }
+
+    let item = tree.get_nodes().first()?.get_item().as_ref()?;
+    if item.get_type() != TokenType::Integer {
+        return None;
+    }
+
+    let value = item.get_value().parse::<u16>().ok()?;
+
+    if value > 1 && value.is_power_of_two() {
+        Some(value.trailing_zeros())
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parser::Expression, tokenizer::Tokenizer};
+
+    fn fold(code: &str) -> Option<Constant> {
+        let tokenizer = Tokenizer::new(code);
+        let tree = Expression::build(&tokenizer).unwrap();
+
+        fold_expression(&tree)
+    }
+
+    #[test]
+    fn fold_boolean_ops() {
+        assert_eq!(fold("true & false"), Some(Constant::Boolean(false)));
+        assert_eq!(fold("true | false"), Some(Constant::Boolean(true)));
+        assert_eq!(fold("~true"), Some(Constant::Boolean(false)));
+        assert_eq!(
+            fold("~(false | false) & true"),
+            Some(Constant::Boolean(true))
+        );
+    }
+
+    #[test]
+    fn fold_skips_non_constant_terms() {
+        assert_eq!(fold("true & x"), None);
+        assert_eq!(fold("1 + x"), None);
+        assert_eq!(fold("true = false"), None);
+        assert_eq!(fold("1 / 0"), None);
+    }
+
+    #[test]
+    fn fold_integer_ops() {
+        assert_eq!(fold("1 + 2"), Some(Constant::Integer(3)));
+        assert_eq!(fold("1 + 2 * 3"), Some(Constant::Integer(9)));
+        assert_eq!(fold("-(5)"), Some(Constant::Integer(-5)));
+        assert_eq!(fold("~0"), Some(Constant::Integer(-1)));
+        assert_eq!(fold("32767 + 1"), Some(Constant::Integer(-32768)));
+        assert_eq!(fold("2 < 3"), Some(Constant::Boolean(true)));
+    }
+
+    #[test]
+    fn fold_division_truncates_toward_zero() {
+        assert_eq!(fold("7 / 2"), Some(Constant::Integer(3)));
+        assert_eq!(fold("(-7) / 2"), Some(Constant::Integer(-3)));
+        assert_eq!(fold("7 / (-2)"), Some(Constant::Integer(-3)));
+        assert_eq!(fold("(-7) / (-2)"), Some(Constant::Integer(3)));
+        assert_eq!(fold("(-1) / 2"), Some(Constant::Integer(0)));
+    }
+
+    #[test]
+    fn shift_amount_for_powers_of_two() {
+        let shift = |code: &str| {
+            let tokenizer = Tokenizer::new(code);
+            let tree = Expression::build(&tokenizer).unwrap();
+
+            shift_amount(tree.get_nodes().first().unwrap())
+        };
+
+        assert_eq!(shift("2"), Some(1));
+        assert_eq!(shift("1024"), Some(10));
+        assert_eq!(shift("1"), None);
+        assert_eq!(shift("6"), None);
+        assert_eq!(shift("x"), None);
+    }
+}
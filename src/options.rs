@@ -0,0 +1,777 @@
+use crate::diagnostics::DEFAULT_MAX_ERRORS;
+use crate::tokenizer::{Limits, DEFAULT_TAB_WIDTH};
+use crate::writer::{LabelNumbering, ReturnConvention, Target, WriterOptions};
+
+pub struct Options {
+    pub path: String,
+    pub debug: bool,
+    pub token_cache: bool,
+    pub split: bool,
+    pub labels: bool,
+    pub unresolved: bool,
+    pub compact: bool,
+    pub combined_xml: bool,
+    pub symbols_csv: bool,
+    pub lint: bool,
+    pub lint_whitespace: bool,
+    pub lint_naming: bool,
+    pub warnings_as_errors: bool,
+    pub verbose: bool,
+    pub color: Option<bool>,
+    pub time: bool,
+    pub summary_json: bool,
+    pub opcode_stats: bool,
+    pub repl: bool,
+    pub max_errors: usize,
+    pub tab_width: usize,
+    pub limits: Limits,
+    pub max_depth: usize,
+    pub bootstrap: Option<String>,
+    pub entry: String,
+    pub stdin_name: String,
+    pub writer_options: WriterOptions,
+}
+
+impl Options {
+    pub fn parse(args: &[String]) -> Result<Options, String> {
+        let mut path: Option<String> = None;
+        let mut debug = false;
+        let mut token_cache = false;
+        let mut split = false;
+        let mut labels = false;
+        let mut unresolved = false;
+        let mut compact = false;
+        let mut combined_xml = false;
+        let mut symbols_csv = false;
+        let mut lint = false;
+        let mut lint_whitespace = false;
+        let mut lint_naming = false;
+        let mut warnings_as_errors = false;
+        let mut verbose = false;
+        let mut color = None;
+        let mut time = false;
+        let mut summary_json = false;
+        let mut opcode_stats = false;
+        let mut fast = false;
+        let mut repl = false;
+        let mut max_errors = DEFAULT_MAX_ERRORS;
+        let mut tab_width = DEFAULT_TAB_WIDTH;
+        let mut limits = Limits::default();
+        let mut max_depth = 0;
+        let mut bootstrap = None;
+        let mut entry = String::from("Sys.init");
+        let mut stdin_name = String::from("<stdin>");
+        let mut writer_options = WriterOptions::default();
+
+        let mut arguments = args.iter().skip(1);
+
+        while let Some(arg) = arguments.next() {
+            match arg.as_str() {
+                "--token-cache" => token_cache = true,
+                "--split" => split = true,
+                "--labels" => labels = true,
+                "--unresolved" => unresolved = true,
+                "--compact" => compact = true,
+                "--combined-xml" => combined_xml = true,
+                "--symbols-csv" => symbols_csv = true,
+                "--lint" => lint = true,
+                "--lint-whitespace" => lint_whitespace = true,
+                "--lint-naming" => lint_naming = true,
+                "--warnings-as-errors" => warnings_as_errors = true,
+                "--verbose" => verbose = true,
+                "--color" => color = Some(true),
+                "--no-color" => color = Some(false),
+                "--time" => time = true,
+                "--summary-json" => summary_json = true,
+                "--opcode-stats" => opcode_stats = true,
+                "--fast" => fast = true,
+                "--repl" => repl = true,
+                "--max-errors" => {
+                    max_errors = arguments
+                        .next()
+                        .and_then(|value| value.parse::<usize>().ok())
+                        .filter(|value| *value > 0)
+                        .ok_or_else(|| String::from("--max-errors expects a positive number"))?
+                }
+                "--max-line-length" => {
+                    limits.max_line_length = arguments
+                        .next()
+                        .and_then(|value| value.parse::<usize>().ok())
+                        .filter(|value| *value > 0)
+                        .ok_or_else(|| {
+                            String::from("--max-line-length expects a positive number")
+                        })?
+                }
+                "--max-token-length" => {
+                    limits.max_token_length = arguments
+                        .next()
+                        .and_then(|value| value.parse::<usize>().ok())
+                        .filter(|value| *value > 0)
+                        .ok_or_else(|| {
+                            String::from("--max-token-length expects a positive number")
+                        })?
+                }
+                "--tab-width" => {
+                    tab_width = arguments
+                        .next()
+                        .and_then(|value| value.parse::<usize>().ok())
+                        .filter(|value| *value > 0)
+                        .ok_or_else(|| String::from("--tab-width expects a positive number"))?
+                }
+                "--max-depth" => {
+                    max_depth = arguments
+                        .next()
+                        .and_then(|value| value.parse::<usize>().ok())
+                        .ok_or_else(|| String::from("--max-depth expects a number"))?
+                }
+                "--max-locals" => {
+                    writer_options.max_locals = Some(
+                        arguments
+                            .next()
+                            .and_then(|value| value.parse::<usize>().ok())
+                            .ok_or_else(|| String::from("--max-locals expects a number"))?,
+                    )
+                }
+                "--max-arguments" => {
+                    writer_options.max_arguments = Some(
+                        arguments
+                            .next()
+                            .and_then(|value| value.parse::<usize>().ok())
+                            .ok_or_else(|| String::from("--max-arguments expects a number"))?,
+                    )
+                }
+                "--bootstrap" => {
+                    bootstrap = Some(
+                        arguments
+                            .next()
+                            .cloned()
+                            .ok_or_else(|| String::from("--bootstrap expects a file name"))?,
+                    );
+                }
+                "--entry" => {
+                    entry = arguments
+                        .next()
+                        .cloned()
+                        .ok_or_else(|| String::from("--entry expects a function name"))?;
+                }
+                "--stdin-name" => {
+                    stdin_name = arguments
+                        .next()
+                        .cloned()
+                        .ok_or_else(|| String::from("--stdin-name expects a name"))?;
+                }
+                "--fold-constants" => writer_options.fold_constants = true,
+                "--auto-return-this" => writer_options.auto_return_this = true,
+                "--end-markers" => writer_options.end_function_marker = true,
+                "--dedup-strings" => writer_options.dedup_strings = true,
+                "--string-class" => {
+                    writer_options.string_class = arguments
+                        .next()
+                        .cloned()
+                        .ok_or_else(|| String::from("--string-class expects a class name"))?;
+                }
+                "--comment-prefix" => {
+                    writer_options.comment_prefix = arguments
+                        .next()
+                        .cloned()
+                        .ok_or_else(|| String::from("--comment-prefix expects a prefix"))?;
+                }
+                "--return-convention" => {
+                    let name = arguments
+                        .next()
+                        .ok_or_else(|| String::from("--return-convention expects a name"))?;
+
+                    writer_options.return_convention = ReturnConvention::from_name(name)
+                        .ok_or_else(|| format!("Unknown return convention: {}", name))?;
+                }
+                "--label-numbering" => {
+                    let name = arguments
+                        .next()
+                        .ok_or_else(|| String::from("--label-numbering expects a mode"))?;
+
+                    writer_options.label_numbering = LabelNumbering::from_name(name)
+                        .ok_or_else(|| format!("Unknown label numbering: {}", name))?;
+                }
+                "--target" => {
+                    let name = arguments
+                        .next()
+                        .ok_or_else(|| String::from("--target expects a runtime name"))?;
+
+                    writer_options.target = Target::from_name(name)
+                        .ok_or_else(|| format!("Unknown target: {}", name))?;
+                }
+                value if value.starts_with("--") => {
+                    return Err(format!("Unknown option: {}", value))
+                }
+                value => {
+                    if path.is_none() {
+                        path = Some(String::from(value));
+                    } else {
+                        debug = true;
+                    }
+                }
+            }
+        }
+
+        let path = match path {
+            Some(path) => path,
+            None if repl => String::new(),
+            None => return Err(String::from("Please supply a folder or file name")),
+        };
+
+        // `--fast` turns off the optional checks: the lints of `--lint`,
+        // `--lint-whitespace` and `--lint-naming`, the unknown class check of
+        // `--unresolved`, and the `--max-locals` and `--max-arguments` limits.
+        // The checks done while parsing (balanced brackets, duplicate
+        // subroutines, `this` inside a function) still run, since skipping
+        // them would emit wrong VM code instead of errors.
+        if fast {
+            unresolved = false;
+            lint = false;
+            lint_whitespace = false;
+            lint_naming = false;
+            writer_options.max_locals = None;
+            writer_options.max_arguments = None;
+        }
+
+        Ok(Options {
+            path,
+            debug,
+            token_cache,
+            split,
+            labels,
+            unresolved,
+            compact,
+            combined_xml,
+            symbols_csv,
+            lint,
+            lint_whitespace,
+            lint_naming,
+            warnings_as_errors,
+            verbose,
+            color,
+            time,
+            summary_json,
+            opcode_stats,
+            repl,
+            max_errors,
+            tab_width,
+            limits,
+            max_depth,
+            bootstrap,
+            entry,
+            stdin_name,
+            writer_options,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn to_args(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| String::from(*value)).collect()
+    }
+
+    #[test]
+    fn parse_path_and_debug() {
+        let options = Options::parse(&to_args(&["jack_compiler", "Main.jack", "debug"])).unwrap();
+
+        assert_eq!(options.path, "Main.jack");
+        assert!(options.debug);
+        assert!(!options.token_cache);
+    }
+
+    #[test]
+    fn parse_unresolved() {
+        let options =
+            Options::parse(&to_args(&["jack_compiler", "--unresolved", "Square"])).unwrap();
+
+        assert_eq!(options.path, "Square");
+        assert!(options.unresolved);
+    }
+
+    #[test]
+    fn parse_summary_json() {
+        let options =
+            Options::parse(&to_args(&["jack_compiler", "Square", "--summary-json"])).unwrap();
+
+        assert_eq!(options.path, "Square");
+        assert!(options.summary_json);
+    }
+
+    #[test]
+    fn parse_opcode_stats() {
+        let options =
+            Options::parse(&to_args(&["jack_compiler", "--opcode-stats", "Main.jack"])).unwrap();
+
+        assert_eq!(options.path, "Main.jack");
+        assert!(options.opcode_stats);
+    }
+
+    #[test]
+    fn parse_token_cache() {
+        let options =
+            Options::parse(&to_args(&["jack_compiler", "--token-cache", "Square"])).unwrap();
+
+        assert_eq!(options.path, "Square");
+        assert!(!options.debug);
+        assert!(options.token_cache);
+    }
+
+    #[test]
+    fn parse_split() {
+        let options = Options::parse(&to_args(&["jack_compiler", "Main.jack", "--split"])).unwrap();
+
+        assert!(options.split);
+        assert!(!options.debug);
+    }
+
+    #[test]
+    fn parse_labels() {
+        let options =
+            Options::parse(&to_args(&["jack_compiler", "Main.jack", "--labels"])).unwrap();
+
+        assert!(options.labels);
+        assert!(!options.split);
+    }
+
+    #[test]
+    fn parse_compact() {
+        let options =
+            Options::parse(&to_args(&["jack_compiler", "Main.jack", "--compact"])).unwrap();
+
+        assert!(options.compact);
+    }
+
+    #[test]
+    fn parse_combined_xml() {
+        let options =
+            Options::parse(&to_args(&["jack_compiler", "--combined-xml", "Square"])).unwrap();
+
+        assert!(options.combined_xml);
+        assert!(!options.debug);
+    }
+
+    #[test]
+    fn parse_symbols_csv() {
+        let options =
+            Options::parse(&to_args(&["jack_compiler", "Main.jack", "--symbols-csv"])).unwrap();
+
+        assert!(options.symbols_csv);
+    }
+
+    #[test]
+    fn parse_lint() {
+        let options = Options::parse(&to_args(&["jack_compiler", "--lint", "Main.jack"])).unwrap();
+
+        assert!(options.lint);
+        assert!(!options.warnings_as_errors);
+        assert_eq!(options.path, "Main.jack");
+    }
+
+    #[test]
+    fn parse_lint_whitespace() {
+        let options = Options::parse(&to_args(&[
+            "jack_compiler",
+            "Main.jack",
+            "--lint-whitespace",
+        ]))
+        .unwrap();
+
+        assert!(options.lint_whitespace);
+        assert!(!options.lint);
+    }
+
+    #[test]
+    fn parse_lint_naming() {
+        let options =
+            Options::parse(&to_args(&["jack_compiler", "Main.jack", "--lint-naming"])).unwrap();
+
+        assert!(options.lint_naming);
+        assert!(!options.lint);
+    }
+
+    #[test]
+    fn parse_warnings_as_errors() {
+        let options = Options::parse(&to_args(&[
+            "jack_compiler",
+            "Main.jack",
+            "--lint",
+            "--warnings-as-errors",
+        ]))
+        .unwrap();
+
+        assert!(options.warnings_as_errors);
+    }
+
+    #[test]
+    fn parse_verbose() {
+        let options =
+            Options::parse(&to_args(&["jack_compiler", "Main.jack", "--verbose"])).unwrap();
+
+        assert!(options.verbose);
+        assert!(!options.lint);
+    }
+
+    #[test]
+    fn parse_color() {
+        let options = Options::parse(&to_args(&["jack_compiler", "Main.jack"])).unwrap();
+        assert_eq!(options.color, None);
+
+        let options = Options::parse(&to_args(&["jack_compiler", "Main.jack", "--color"])).unwrap();
+        assert_eq!(options.color, Some(true));
+
+        let options =
+            Options::parse(&to_args(&["jack_compiler", "Main.jack", "--no-color"])).unwrap();
+        assert_eq!(options.color, Some(false));
+    }
+
+    #[test]
+    fn parse_time() {
+        let options = Options::parse(&to_args(&["jack_compiler", "--time", "Square"])).unwrap();
+
+        assert!(options.time);
+        assert_eq!(options.path, "Square");
+    }
+
+    #[test]
+    fn parse_fast() {
+        let options = Options::parse(&to_args(&[
+            "jack_compiler",
+            "Main.jack",
+            "--lint",
+            "--unresolved",
+            "--max-locals",
+            "4",
+            "--fast",
+        ]))
+        .unwrap();
+
+        assert!(!options.lint);
+        assert!(!options.unresolved);
+        assert_eq!(options.writer_options.max_locals, None);
+        assert_eq!(options.writer_options.max_arguments, None);
+    }
+
+    #[test]
+    fn parse_repl_without_path() {
+        let options = Options::parse(&to_args(&["jack_compiler", "--repl"])).unwrap();
+
+        assert!(options.repl);
+        assert_eq!(options.path, "");
+    }
+
+    #[test]
+    fn parse_fold_constants() {
+        let options = Options::parse(&to_args(&[
+            "jack_compiler",
+            "Main.jack",
+            "--fold-constants",
+        ]))
+        .unwrap();
+
+        assert!(options.writer_options.fold_constants);
+    }
+
+    #[test]
+    fn parse_auto_return_this() {
+        let options = Options::parse(&to_args(&[
+            "jack_compiler",
+            "Main.jack",
+            "--auto-return-this",
+        ]))
+        .unwrap();
+
+        assert!(options.writer_options.auto_return_this);
+        assert!(!options.writer_options.fold_constants);
+    }
+
+    #[test]
+    fn parse_dedup_strings() {
+        let options =
+            Options::parse(&to_args(&["jack_compiler", "Main.jack", "--dedup-strings"])).unwrap();
+
+        assert!(options.writer_options.dedup_strings);
+    }
+
+    #[test]
+    fn parse_end_markers() {
+        let options =
+            Options::parse(&to_args(&["jack_compiler", "Main.jack", "--end-markers"])).unwrap();
+
+        assert!(options.writer_options.end_function_marker);
+    }
+
+    #[test]
+    fn parse_target() {
+        let options = Options::parse(&to_args(&["jack_compiler", "Main.jack"])).unwrap();
+        assert_eq!(options.writer_options.target, Target::Hack);
+
+        let options = Options::parse(&to_args(&[
+            "jack_compiler",
+            "--target",
+            "shift-vm",
+            "Main.jack",
+        ]))
+        .unwrap();
+        assert_eq!(options.writer_options.target, Target::ShiftVm);
+
+        let result = Options::parse(&to_args(&["jack_compiler", "Main.jack", "--target", "x86"]));
+        assert_eq!(result.err(), Some(String::from("Unknown target: x86")));
+    }
+
+    #[test]
+    fn parse_string_class() {
+        let options = Options::parse(&to_args(&["jack_compiler", "Main.jack"])).unwrap();
+        assert_eq!(options.writer_options.string_class, "String");
+
+        let options = Options::parse(&to_args(&[
+            "jack_compiler",
+            "Main.jack",
+            "--string-class",
+            "Text",
+        ]))
+        .unwrap();
+        assert_eq!(options.writer_options.string_class, "Text");
+    }
+
+    #[test]
+    fn parse_comment_prefix() {
+        let options = Options::parse(&to_args(&["jack_compiler", "Main.jack"])).unwrap();
+        assert_eq!(options.writer_options.comment_prefix, "//");
+
+        let options = Options::parse(&to_args(&[
+            "jack_compiler",
+            "Main.jack",
+            "--comment-prefix",
+            ";",
+        ]))
+        .unwrap();
+        assert_eq!(options.writer_options.comment_prefix, ";");
+    }
+
+    #[test]
+    fn parse_limits() {
+        let options = Options::parse(&to_args(&["jack_compiler", "Main.jack"])).unwrap();
+
+        assert_eq!(options.limits, Limits::default());
+
+        let options = Options::parse(&to_args(&[
+            "jack_compiler",
+            "Main.jack",
+            "--max-line-length",
+            "120",
+            "--max-token-length",
+            "32",
+        ]))
+        .unwrap();
+
+        assert_eq!(options.limits.max_line_length, 120);
+        assert_eq!(options.limits.max_token_length, 32);
+
+        let result = Options::parse(&to_args(&[
+            "jack_compiler",
+            "Main.jack",
+            "--max-token-length",
+            "0",
+        ]));
+
+        assert_eq!(
+            result.err(),
+            Some(String::from("--max-token-length expects a positive number"))
+        );
+    }
+
+    #[test]
+    fn parse_tab_width() {
+        let options = Options::parse(&to_args(&["jack_compiler", "Main.jack"])).unwrap();
+        assert_eq!(options.tab_width, 1);
+
+        let options = Options::parse(&to_args(&[
+            "jack_compiler",
+            "Main.jack",
+            "--tab-width",
+            "8",
+        ]))
+        .unwrap();
+        assert_eq!(options.tab_width, 8);
+    }
+
+    #[test]
+    fn parse_max_depth() {
+        let options = Options::parse(&to_args(&["jack_compiler", "Square"])).unwrap();
+        assert_eq!(options.max_depth, 0);
+
+        let options =
+            Options::parse(&to_args(&["jack_compiler", "Square", "--max-depth", "3"])).unwrap();
+        assert_eq!(options.max_depth, 3);
+
+        let result = Options::parse(&to_args(&["jack_compiler", "Square", "--max-depth"]));
+        assert_eq!(
+            result.err(),
+            Some(String::from("--max-depth expects a number"))
+        );
+    }
+
+    #[test]
+    fn parse_max_locals() {
+        let options = Options::parse(&to_args(&["jack_compiler", "Main.jack"])).unwrap();
+        assert_eq!(options.writer_options.max_locals, Some(256));
+
+        let options = Options::parse(&to_args(&[
+            "jack_compiler",
+            "Main.jack",
+            "--max-locals",
+            "16",
+        ]))
+        .unwrap();
+        assert_eq!(options.writer_options.max_locals, Some(16));
+    }
+
+    #[test]
+    fn parse_max_arguments() {
+        let options = Options::parse(&to_args(&["jack_compiler", "Main.jack"])).unwrap();
+        assert_eq!(options.writer_options.max_arguments, Some(32));
+
+        let options = Options::parse(&to_args(&[
+            "jack_compiler",
+            "Main.jack",
+            "--max-arguments",
+            "8",
+        ]))
+        .unwrap();
+        assert_eq!(options.writer_options.max_arguments, Some(8));
+    }
+
+    #[test]
+    fn parse_bootstrap() {
+        let options = Options::parse(&to_args(&["jack_compiler", "Square"])).unwrap();
+        assert_eq!(options.bootstrap, None);
+        assert_eq!(options.entry, "Sys.init");
+
+        let options = Options::parse(&to_args(&[
+            "jack_compiler",
+            "Square",
+            "--bootstrap",
+            "Square/Program.vm",
+            "--entry",
+            "Main.main",
+        ]))
+        .unwrap();
+        assert_eq!(options.bootstrap, Some(String::from("Square/Program.vm")));
+        assert_eq!(options.entry, "Main.main");
+    }
+
+    #[test]
+    fn parse_stdin_name() {
+        let options = Options::parse(&to_args(&["jack_compiler", "-"])).unwrap();
+        assert_eq!(options.path, "-");
+        assert_eq!(options.stdin_name, "<stdin>");
+
+        let options = Options::parse(&to_args(&[
+            "jack_compiler",
+            "-",
+            "--stdin-name",
+            "Main.jack",
+        ]))
+        .unwrap();
+        assert_eq!(options.stdin_name, "Main.jack");
+    }
+
+    #[test]
+    fn parse_return_convention() {
+        let options = Options::parse(&to_args(&[
+            "jack_compiler",
+            "Main.jack",
+            "--return-convention",
+            "caller-cleanup",
+        ]))
+        .unwrap();
+        assert_eq!(
+            options.writer_options.return_convention,
+            ReturnConvention::CallerCleanup
+        );
+
+        let result = Options::parse(&to_args(&[
+            "jack_compiler",
+            "Main.jack",
+            "--return-convention",
+            "pascal",
+        ]));
+        assert_eq!(
+            result.err(),
+            Some(String::from("Unknown return convention: pascal"))
+        );
+    }
+
+    #[test]
+    fn parse_label_numbering() {
+        let options = Options::parse(&to_args(&["jack_compiler", "Main.jack"])).unwrap();
+        assert_eq!(
+            options.writer_options.label_numbering,
+            LabelNumbering::Class
+        );
+
+        let options = Options::parse(&to_args(&[
+            "jack_compiler",
+            "Main.jack",
+            "--label-numbering",
+            "official",
+        ]))
+        .unwrap();
+        assert_eq!(
+            options.writer_options.label_numbering,
+            LabelNumbering::Official
+        );
+    }
+
+    #[test]
+    fn parse_max_errors() {
+        let options = Options::parse(&to_args(&["jack_compiler", "Main.jack"])).unwrap();
+        assert_eq!(options.max_errors, 20);
+
+        let options = Options::parse(&to_args(&[
+            "jack_compiler",
+            "--max-errors",
+            "5",
+            "Main.jack",
+        ]))
+        .unwrap();
+        assert_eq!(options.max_errors, 5);
+        assert_eq!(options.path, "Main.jack");
+    }
+
+    #[test]
+    fn parse_invalid_max_errors() {
+        let result = Options::parse(&to_args(&[
+            "jack_compiler",
+            "Main.jack",
+            "--max-errors",
+            "0",
+        ]));
+
+        assert_eq!(
+            result.err(),
+            Some(String::from("--max-errors expects a positive number"))
+        );
+    }
+
+    #[test]
+    fn parse_missing_path() {
+        let result = Options::parse(&to_args(&["jack_compiler"]));
+
+        assert_eq!(
+            result.err(),
+            Some(String::from("Please supply a folder or file name"))
+        );
+    }
+
+    #[test]
+    fn parse_unknown_option() {
+        let result = Options::parse(&to_args(&["jack_compiler", "Main.jack", "--nope"]));
+
+        assert_eq!(result.err(), Some(String::from("Unknown option: --nope")));
+    }
+}
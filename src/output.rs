@@ -0,0 +1,72 @@
+use std::collections::HashMap;
+use std::fs;
+
+/// Where generated files go, so they can be kept in memory instead of written
+/// to disk.
+pub trait OutputSink {
+    fn write_bytes(&mut self, name: &str, contents: &[u8]);
+
+    fn write(&mut self, name: &str, contents: &str) {
+        self.write_bytes(name, contents.as_bytes());
+    }
+}
+
+/// Writes each file to disk, at its name.
+pub struct FileSink {}
+
+impl FileSink {
+    pub fn new() -> FileSink {
+        FileSink {}
+    }
+}
+
+impl Default for FileSink {
+    fn default() -> Self {
+        FileSink::new()
+    }
+}
+
+impl OutputSink for FileSink {
+    fn write_bytes(&mut self, name: &str, contents: &[u8]) {
+        fs::write(name, contents).expect("Something failed on write file to disk");
+    }
+}
+
+/// Keeps each file in memory, replacing earlier files with the same name.
+#[derive(Default)]
+pub struct MemorySink {
+    files: HashMap<String, Vec<u8>>,
+}
+
+impl MemorySink {
+    pub fn new() -> MemorySink {
+        MemorySink {
+            files: HashMap::new(),
+        }
+    }
+
+    /// The contents of a text file, or `None` if it was not written or is
+    /// not valid UTF-8.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.get_bytes(name)
+            .and_then(|contents| std::str::from_utf8(contents).ok())
+    }
+
+    pub fn get_bytes(&self, name: &str) -> Option<&[u8]> {
+        self.files.get(name).map(|contents| contents.as_slice())
+    }
+
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+}
+
+impl OutputSink for MemorySink {
+    fn write_bytes(&mut self, name: &str, contents: &[u8]) {
+        self.files.insert(String::from(name), contents.to_vec());
+    }
+}
@@ -1,6 +1,11 @@
 use std::collections::HashMap;
 
-use crate::tokenizer::{TokenItem, TokenType, Tokenizer, UNARY_OP_SYMBOLS};
+use crate::diagnostics::Diagnostics;
+use crate::error::CompileError;
+use crate::signature::subroutine_signatures;
+use crate::tokenizer::{
+    Position, TokenItem, TokenType, Tokenizer, KEYWORD_CONSTANTS, UNARY_OP_SYMBOLS,
+};
 
 pub struct TokenTreeItem {
     name: Option<String>,
@@ -51,6 +56,44 @@ impl TokenTreeItem {
     pub fn get_nodes(&self) -> &Vec<TokenTreeItem> {
         &self.nodes
     }
+
+    /// The symbols visible in a `class` or `subroutineDec` node.
+    pub fn get_symbol_table(&self) -> &Option<SymbolTable> {
+        &self.symbol_table
+    }
+
+    /// Counts this node and all of its descendants.
+    pub fn count_nodes(&self) -> usize {
+        1 + self
+            .nodes
+            .iter()
+            .map(|node| node.count_nodes())
+            .sum::<usize>()
+    }
+
+    /// Compares names, token types/values and children recursively, ignoring
+    /// token positions and attached symbol tables.
+    pub fn structurally_eq(&self, other: &TokenTreeItem) -> bool {
+        if self.name != other.name || self.nodes.len() != other.nodes.len() {
+            return false;
+        }
+
+        let same_item = match (&self.item, &other.item) {
+            (Some(item), Some(other_item)) => {
+                item.get_type() == other_item.get_type()
+                    && item.get_value() == other_item.get_value()
+            }
+            (None, None) => true,
+            _ => false,
+        };
+
+        same_item
+            && self
+                .nodes
+                .iter()
+                .zip(other.nodes.iter())
+                .all(|(node, other_node)| node.structurally_eq(other_node))
+    }
 }
 
 #[derive(Eq, PartialEq, Hash, Debug, Clone, Copy)]
@@ -62,7 +105,7 @@ enum SymbolType {
 }
 
 #[derive(Eq, PartialEq, Hash, Debug, Clone)]
-struct SymbolItem {
+pub struct SymbolItem {
     id: usize,
     name: String,
     symbol_type: SymbolType,
@@ -87,6 +130,11 @@ impl SymbolItem {
         }
     }
 
+    pub fn get_name(&self) -> &String {
+        &self.name
+    }
+
+    /// The VM segment of the symbol: `this`, `static`, `local` or `argument`.
     pub fn get_type_as_str(&self) -> String {
         let result = match self.symbol_type {
             SymbolType::Argument => "argument",
@@ -107,6 +155,14 @@ impl SymbolItem {
     }
 }
 
+/// The size of a `SymbolTable` at some point, to roll it back to.
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    symbols: usize,
+    types: HashMap<SymbolType, usize>,
+}
+
+#[derive(Clone)]
 pub struct SymbolTable {
     symbols: Vec<SymbolItem>,
     indexes: HashMap<String, usize>,
@@ -117,10 +173,10 @@ impl SymbolTable {
     pub fn new() -> SymbolTable {
         let mut types = HashMap::new();
 
-        types.insert(SymbolType::Field, 0 as usize);
-        types.insert(SymbolType::StaticType, 0 as usize);
-        types.insert(SymbolType::Local, 0 as usize);
-        types.insert(SymbolType::Argument, 0 as usize);
+        types.insert(SymbolType::Field, 0_usize);
+        types.insert(SymbolType::StaticType, 0_usize);
+        types.insert(SymbolType::Local, 0_usize);
+        types.insert(SymbolType::Argument, 0_usize);
 
         SymbolTable {
             symbols: Vec::new(),
@@ -129,18 +185,14 @@ impl SymbolTable {
         }
     }
 
-    pub fn clone(&self) -> SymbolTable {
-        SymbolTable {
-            symbols: Vec::from(self.symbols.clone()),
-            indexes: HashMap::from(self.indexes.clone()),
-            types: HashMap::from(self.types.clone()),
-        }
-    }
-
     pub fn count_fields(&self) -> usize {
         *self.types.get(&SymbolType::Field).unwrap()
     }
 
+    pub fn static_count(&self) -> usize {
+        *self.types.get(&SymbolType::StaticType).unwrap()
+    }
+
     pub fn increase_arguments(&mut self) {
         for i in &mut self.symbols {
             if i.symbol_type == SymbolType::Argument {
@@ -150,19 +202,24 @@ impl SymbolTable {
     }
 
     pub fn add(&mut self, symbol_type: &str, kind: &str, name: &str) {
+        self.define(symbol_type, kind, name);
+    }
+
+    /// Adds a symbol like `add`, returning its position inside its segment.
+    pub fn define(&mut self, symbol_type: &str, kind: &str, name: &str) -> usize {
         let symbol_type = match symbol_type {
             "field" => SymbolType::Field,
             "static" => SymbolType::StaticType,
             "var" => SymbolType::Local,
             "argument" => SymbolType::Argument,
-            v => panic!(format!("Invalid symbol type: {}", v)),
+            v => panic!("Invalid symbol type: {}", v),
         };
 
         if self.indexes.contains_key(name) {
-            panic!(format!("Symbol already exists on symbol table: {}", name));
+            panic!("Symbol already exists on symbol table: {}", name);
         }
 
-        let position = self.types.get(&symbol_type).unwrap().clone();
+        let position = *self.types.get(&symbol_type).unwrap();
         *self.types.entry(symbol_type).or_insert(1) += 1;
 
         let id = self.symbols.len();
@@ -175,19 +232,42 @@ impl SymbolTable {
         ));
 
         self.indexes.insert(String::from(name), id);
+
+        position
+    }
+
+    /// The symbols in the order they were added.
+    pub fn iter(&self) -> std::slice::Iter<'_, SymbolItem> {
+        self.symbols.iter()
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            symbols: self.symbols.len(),
+            types: self.types.clone(),
+        }
+    }
+
+    /// Forgets every symbol added after `snapshot` was taken, so a tentative
+    /// declaration can be undone.
+    pub fn restore(&mut self, snapshot: Snapshot) {
+        for symbol in self.symbols.drain(snapshot.symbols..) {
+            self.indexes.remove(&symbol.name);
+        }
+
+        self.types = snapshot.types;
     }
 
     fn get(&self, name: &str) -> &SymbolItem {
-        let index = self
+        let index = *self
             .indexes
             .get(name)
-            .expect(format!("Name not found on indexes: {}", name).as_str())
-            .clone();
+            .unwrap_or_else(|| panic!("Name not found on indexes: {}", name));
         self.symbols.get(index).unwrap()
     }
 
     pub fn contains(&self, name: &str) -> bool {
-        self.indexes.get(name).is_some()
+        self.indexes.contains_key(name)
     }
 
     pub fn get_pop(&self, name: &str) -> String {
@@ -210,33 +290,296 @@ impl SymbolTable {
     }
 }
 
+impl Default for SymbolTable {
+    fn default() -> Self {
+        SymbolTable::new()
+    }
+}
+
 pub struct ClassNode {}
 
 impl ClassNode {
-    pub fn build(tokenizer: &Tokenizer) -> TokenTreeItem {
+    pub fn build(tokenizer: &Tokenizer) -> Result<TokenTreeItem, CompileError> {
+        ClassNode::build_with(tokenizer, None)
+    }
+
+    /// Parses like `build`, but records a broken subroutine on `diagnostics`
+    /// and resumes at the next one, so a single run reports several errors.
+    pub fn build_recovering(
+        tokenizer: &Tokenizer,
+        diagnostics: &mut Diagnostics,
+    ) -> Option<TokenTreeItem> {
+        match ClassNode::build_with(tokenizer, Some(diagnostics)) {
+            Ok(root) if !diagnostics.has_errors() => Some(root),
+            Ok(_) => None,
+            Err(error) => {
+                diagnostics.push(error);
+                None
+            }
+        }
+    }
+
+    fn build_with(
+        tokenizer: &Tokenizer,
+        diagnostics: Option<&mut Diagnostics>,
+    ) -> Result<TokenTreeItem, CompileError> {
         let mut root = TokenTreeItem::new_root("class");
         let mut symbol_table = SymbolTable::new();
 
+        check_braces(tokenizer.get_tokens())?;
+
         tokenizer.reset();
 
-        root.push(tokenizer.consume("class"));
+        root.push(tokenizer.consume("class")?);
 
-        root.push(tokenizer.retrieve_identifier());
+        root.push(retrieve_name(tokenizer)?);
 
-        root.push(tokenizer.consume("{"));
+        root.push(tokenizer.consume("{")?);
 
-        for var_dec in VarDec::build_class(tokenizer, &mut symbol_table) {
+        for var_dec in VarDec::build_class(tokenizer, &mut symbol_table)? {
             root.push_item(var_dec);
         }
 
-        for subroutine in SubroutineDec::build(tokenizer, &symbol_table) {
-            root.push_item(subroutine);
+        match diagnostics {
+            Some(diagnostics) => {
+                for subroutine in
+                    SubroutineDec::build_recovering(tokenizer, &symbol_table, diagnostics)
+                {
+                    root.push_item(subroutine);
+                }
+
+                if diagnostics.has_errors() {
+                    return Ok(root);
+                }
+            }
+            None => {
+                for subroutine in SubroutineDec::build(tokenizer, &symbol_table)? {
+                    root.push_item(subroutine);
+                }
+            }
         }
 
-        root.push(tokenizer.consume("}"));
+        check_duplicate_subroutines(&root)?;
+
+        root.push(tokenizer.consume("}")?);
+        root.set_symbol_table(symbol_table);
+
+        Ok(root)
+    }
+}
+
+/// Two subroutines with one name would both become `function Class.name`,
+/// which the VM cannot link. The check walks the class signatures, the pass
+/// that collects each subroutine's parameters, and runs here rather than in
+/// `VmWriter::build_class` because the writer can only warn or panic.
+fn check_duplicate_subroutines(root: &TokenTreeItem) -> Result<(), CompileError> {
+    let mut declared: HashMap<String, Position> = HashMap::new();
+
+    for signature in subroutine_signatures(root) {
+        if let Some(first) = declared.get(signature.get_name()) {
+            return Err(CompileError::syntax(
+                format!(
+                    "duplicate subroutine '{}', first declared at {}",
+                    signature.get_name(),
+                    first
+                )
+                .as_str(),
+                signature.get_position(),
+            ));
+        }
+
+        declared.insert(signature.get_name().clone(), signature.get_position());
+    }
+
+    Ok(())
+}
+
+/// Fails when a parse loop iteration finished without consuming any token,
+/// which would otherwise make the loop spin forever.
+fn ensure_progress(tokenizer: &Tokenizer, start: usize) -> Result<(), CompileError> {
+    if tokenizer.get_cursor() == start {
+        let position = tokenizer
+            .get_tokens()
+            .get(start)
+            .map(|token| token.get_position())
+            .unwrap_or_else(|| tokenizer.get_last_position());
+
+        return Err(CompileError::NoProgress { position });
+    }
+
+    Ok(())
+}
+
+/// Checks that every `{`, `(` and `[` is closed by its matching bracket,
+/// reporting the first bracket out of place. Parentheses get their own
+/// message, pointing at the `(` left open or at the stray `)`.
+pub fn check_braces(tokens: &[TokenItem]) -> Result<(), CompileError> {
+    let mut open: Vec<&TokenItem> = Vec::new();
+
+    for token in tokens.iter() {
+        if token.get_type() != TokenType::Symbol {
+            continue;
+        }
+
+        let value = token.get_value();
+
+        let expected_open = match value.as_str() {
+            "{" | "(" | "[" => {
+                open.push(token);
+                continue;
+            }
+            "}" => "{",
+            ")" => "(",
+            "]" => "[",
+            _ => continue,
+        };
+
+        match open.pop() {
+            Some(open_token) if open_token.get_value() == expected_open => (),
+            Some(open_token) if open_token.get_value() == "(" => {
+                return Err(unclosed_paren(open_token))
+            }
+            _ if value == ")" => {
+                return Err(CompileError::syntax(
+                    "unbalanced parentheses: unexpected ')'",
+                    token.get_position(),
+                ))
+            }
+            Some(open_token) => {
+                return Err(CompileError::syntax(
+                    format!(
+                        "unclosed '{}', found '{}' at {}",
+                        open_token.get_value(),
+                        value,
+                        token.get_position()
+                    )
+                    .as_str(),
+                    open_token.get_position(),
+                ))
+            }
+            None => {
+                return Err(CompileError::syntax(
+                    format!(
+                        "unexpected '{}' without a matching '{}'",
+                        value, expected_open
+                    )
+                    .as_str(),
+                    token.get_position(),
+                ))
+            }
+        }
+    }
+
+    match open.pop() {
+        Some(open_token) if open_token.get_value() == "(" => Err(unclosed_paren(open_token)),
+        Some(open_token) => Err(CompileError::syntax(
+            format!("unclosed '{}'", open_token.get_value()).as_str(),
+            open_token.get_position(),
+        )),
+        None => Ok(()),
+    }
+}
+
+fn unclosed_paren(open_paren: &TokenItem) -> CompileError {
+    CompileError::syntax(
+        "unbalanced parentheses: expected ')'",
+        open_paren.get_position(),
+    )
+}
+
+fn unexpected_eof(tokenizer: &Tokenizer, context: &str) -> CompileError {
+    CompileError::syntax(
+        format!("unexpected end of file while parsing {}", context).as_str(),
+        tokenizer.get_last_position(),
+    )
+}
+
+/// Reads the type of a variable or parameter, rejecting `void`, which is only
+/// valid as a return type.
+fn retrieve_variable_type(tokenizer: &Tokenizer) -> Result<TokenItem, CompileError> {
+    if let Some(token) = tokenizer.peek_next() {
+        if token.get_type() == TokenType::Keyword && token.get_value() == "void" {
+            return Err(CompileError::syntax(
+                "'void' is not a valid variable type",
+                token.get_position(),
+            ));
+        }
+    }
+
+    tokenizer.retrieve_type()
+}
+
+/// Reads an identifier, rejecting keywords like `class` or `return` that
+/// cannot name anything.
+fn retrieve_name(tokenizer: &Tokenizer) -> Result<TokenItem, CompileError> {
+    if let Some(token) = tokenizer.peek_next() {
+        if token.get_type() == TokenType::Keyword {
+            return Err(CompileError::syntax(
+                format!(
+                    "'{}' is a reserved keyword and cannot be used as an identifier",
+                    token.get_value()
+                )
+                .as_str(),
+                token.get_position(),
+            ));
+        }
+    }
+
+    tokenizer.retrieve_identifier()
+}
+
+/// Adds `identifier` to `symbol_table`, reporting a name declared twice in
+/// the same scope.
+fn declare(
+    symbol_table: &mut SymbolTable,
+    symbol_type: &str,
+    kind: &str,
+    identifier: &TokenItem,
+) -> Result<(), CompileError> {
+    let name = identifier.get_value();
+
+    if symbol_table.contains(name.as_str()) {
+        return Err(CompileError::syntax(
+            format!("'{}' is already declared", name).as_str(),
+            identifier.get_position(),
+        ));
+    }
+
+    symbol_table.add(symbol_type, kind, name.as_str());
+
+    Ok(())
+}
+
+/// Reads the name starting a subroutine call, which may also be `this` when
+/// followed by a `.`, as in `this.move()`.
+fn retrieve_receiver(tokenizer: &Tokenizer) -> Result<TokenItem, CompileError> {
+    match tokenizer.peek_next() {
+        Some(token) if token.get_type() == TokenType::Keyword && token.get_value() == "this" => {
+            let token = tokenizer.get_next().unwrap().clone();
+
+            match tokenizer.peek_next() {
+                Some(next) if next.get_value() == "." => Ok(token),
+                _ => Err(CompileError::syntax(
+                    "expected '.' after 'this'",
+                    token.get_position(),
+                )),
+            }
+        }
+        _ => retrieve_name(tokenizer),
+    }
+}
 
-        root
+/// The first use of the keyword `value` inside `tree`.
+fn find_keyword<'a>(tree: &'a TokenTreeItem, value: &str) -> Option<&'a TokenItem> {
+    if let Some(item) = tree.get_item() {
+        if item.get_type() == TokenType::Keyword && item.get_value() == value {
+            return Some(item);
+        }
     }
+
+    tree.get_nodes()
+        .iter()
+        .find_map(|node| find_keyword(node, value))
 }
 
 pub struct VarDec {}
@@ -245,7 +588,7 @@ impl VarDec {
     pub fn build_class(
         tokenizer: &Tokenizer,
         symbol_table: &mut SymbolTable,
-    ) -> Vec<TokenTreeItem> {
+    ) -> Result<Vec<TokenTreeItem>, CompileError> {
         let mut result = Vec::new();
 
         while let Some(current_token) = tokenizer.peek_next() {
@@ -255,21 +598,24 @@ impl VarDec {
                     "classVarDec",
                     "field",
                     symbol_table,
-                )),
+                )?),
                 "static" => result.push(VarDec::build_field(
                     tokenizer,
                     "classVarDec",
                     "static",
                     symbol_table,
-                )),
+                )?),
                 _ => break,
             }
         }
 
-        result
+        Ok(result)
     }
 
-    pub fn build_var(tokenizer: &Tokenizer, symbol_table: &mut SymbolTable) -> Vec<TokenTreeItem> {
+    pub fn build_var(
+        tokenizer: &Tokenizer,
+        symbol_table: &mut SymbolTable,
+    ) -> Result<Vec<TokenTreeItem>, CompileError> {
         let mut result = Vec::new();
 
         while let Some(current_token) = tokenizer.peek_next() {
@@ -279,12 +625,12 @@ impl VarDec {
                     "varDec",
                     "var",
                     symbol_table,
-                )),
+                )?),
                 _ => break,
             }
         }
 
-        result
+        Ok(result)
     }
 
     fn build_field(
@@ -292,17 +638,17 @@ impl VarDec {
         name: &str,
         descriptor: &str,
         symbol_table: &mut SymbolTable,
-    ) -> TokenTreeItem {
+    ) -> Result<TokenTreeItem, CompileError> {
         let mut root = TokenTreeItem::new_root(name);
 
-        root.push(tokenizer.consume(descriptor));
+        root.push(tokenizer.consume(descriptor)?);
 
-        let field_type = tokenizer.retrieve_type();
+        let field_type = retrieve_variable_type(tokenizer)?;
         let kind = field_type.get_value();
 
-        let identifier = tokenizer.retrieve_identifier();
+        let identifier = retrieve_name(tokenizer)?;
 
-        symbol_table.add(descriptor, kind.as_str(), identifier.get_value().as_str());
+        declare(symbol_table, descriptor, kind.as_str(), &identifier)?;
 
         root.push(field_type);
         root.push(identifier);
@@ -312,9 +658,9 @@ impl VarDec {
                 "," => {
                     root.push(token.clone());
 
-                    let identifier = tokenizer.retrieve_identifier();
+                    let identifier = retrieve_name(tokenizer)?;
 
-                    symbol_table.add(descriptor, kind.as_str(), identifier.get_value().as_str());
+                    declare(symbol_table, descriptor, kind.as_str(), &identifier)?;
 
                     root.push(identifier);
                 }
@@ -322,18 +668,26 @@ impl VarDec {
                     root.push(token.clone());
                     break;
                 }
-                value => panic!(format!("Expecting ',' or ';', but retrieved '{}'", value)),
+                value => {
+                    return Err(CompileError::syntax(
+                        format!("expected ',' or ';', found '{}'", value).as_str(),
+                        token.get_position(),
+                    ))
+                }
             }
         }
 
-        root
+        Ok(root)
     }
 }
 
 struct SubroutineDec {}
 
 impl SubroutineDec {
-    pub fn build(tokenizer: &Tokenizer, symbol_table: &SymbolTable) -> Vec<TokenTreeItem> {
+    pub fn build(
+        tokenizer: &Tokenizer,
+        symbol_table: &SymbolTable,
+    ) -> Result<Vec<TokenTreeItem>, CompileError> {
         let mut result = Vec::new();
 
         while let Some(next_token) = tokenizer.peek_next() {
@@ -341,52 +695,127 @@ impl SubroutineDec {
                 break;
             }
 
-            result.push(SubroutineDec::build_subroutine(tokenizer, &symbol_table));
+            let cursor = tokenizer.get_cursor();
+            result.push(SubroutineDec::build_subroutine(tokenizer, symbol_table)?);
+            ensure_progress(tokenizer, cursor)?;
+        }
+
+        Ok(result)
+    }
+
+    fn build_recovering(
+        tokenizer: &Tokenizer,
+        symbol_table: &SymbolTable,
+        diagnostics: &mut Diagnostics,
+    ) -> Vec<TokenTreeItem> {
+        let mut result = Vec::new();
+
+        while let Some(next_token) = tokenizer.peek_next() {
+            if next_token.get_value() == "}" {
+                break;
+            }
+
+            let cursor = tokenizer.get_cursor();
+            let subroutine = SubroutineDec::build_subroutine(tokenizer, symbol_table)
+                .and_then(|subroutine| ensure_progress(tokenizer, cursor).map(|_| subroutine));
+
+            match subroutine {
+                Ok(subroutine) => result.push(subroutine),
+                Err(error) => {
+                    diagnostics.push(error);
+
+                    if diagnostics.is_full() {
+                        break;
+                    }
+
+                    SubroutineDec::skip_to_next(tokenizer, cursor);
+                }
+            }
         }
 
         result
     }
 
-    pub fn build_subroutine(tokenizer: &Tokenizer, symbol_table: &SymbolTable) -> TokenTreeItem {
+    /// Moves the cursor to the next subroutine declaration (or to the end),
+    /// always leaving the position where the failed subroutine started.
+    fn skip_to_next(tokenizer: &Tokenizer, start: usize) {
+        if tokenizer.get_cursor() == start {
+            tokenizer.get_next();
+        }
+
+        while let Some(next_token) = tokenizer.peek_next() {
+            if next_token.get_type() == TokenType::Keyword
+                && ["constructor", "function", "method"].contains(&next_token.get_value().as_str())
+            {
+                break;
+            }
+
+            tokenizer.get_next();
+        }
+    }
+
+    pub fn build_subroutine(
+        tokenizer: &Tokenizer,
+        symbol_table: &SymbolTable,
+    ) -> Result<TokenTreeItem, CompileError> {
         let mut root = TokenTreeItem::new_root("subroutineDec");
         let mut symbol_table = symbol_table.clone();
 
-        root.push(tokenizer.retrieve_keyword());
-        root.push(tokenizer.retrieve_any(Vec::from([TokenType::Keyword, TokenType::Identifier])));
-        root.push(tokenizer.retrieve_identifier());
-        root.push(tokenizer.consume("("));
+        let kind = tokenizer.retrieve_keyword()?;
+        let is_function = kind.get_value() == "function";
+        root.push(kind);
+        root.push(tokenizer.retrieve_any(Vec::from([TokenType::Keyword, TokenType::Identifier]))?);
+        root.push(retrieve_name(tokenizer)?);
+        root.push(tokenizer.consume("(")?);
 
         root.push_item(SubroutineDec::build_parameters(
             tokenizer,
             &mut symbol_table,
-        ));
+        )?);
 
-        root.push(tokenizer.consume(")"));
+        root.push(tokenizer.consume(")")?);
 
-        root.push_item(SubroutineDec::build_body(tokenizer, &mut symbol_table));
+        let body = SubroutineDec::build_body(tokenizer, &mut symbol_table)?;
+
+        if is_function {
+            if let Some(this) = find_keyword(&body, "this") {
+                return Err(CompileError::syntax(
+                    "'this' is not available in a function",
+                    this.get_position(),
+                ));
+            }
+        }
+
+        root.push_item(body);
 
         root.set_symbol_table(symbol_table);
 
-        root
+        Ok(root)
     }
 
-    fn build_body(tokenizer: &Tokenizer, symbol_table: &mut SymbolTable) -> TokenTreeItem {
+    fn build_body(
+        tokenizer: &Tokenizer,
+        symbol_table: &mut SymbolTable,
+    ) -> Result<TokenTreeItem, CompileError> {
         let mut root = TokenTreeItem::new_root("subroutineBody");
 
-        root.push(tokenizer.consume("{"));
+        root.push(tokenizer.consume("{")?);
 
-        for var_dec in VarDec::build_var(tokenizer, symbol_table) {
+        for var_dec in VarDec::build_var(tokenizer, symbol_table)? {
             root.push_item(var_dec);
         }
 
-        root.push_item(Statement::build_list(tokenizer));
+        root.push_item(Statement::build_list(tokenizer)?);
 
-        root.push(tokenizer.consume("}"));
+        root.push(tokenizer.consume("}")?);
 
-        root
+        Ok(root)
     }
 
-    fn build_parameters(tokenizer: &Tokenizer, symbol_table: &mut SymbolTable) -> TokenTreeItem {
+    fn build_parameters(
+        tokenizer: &Tokenizer,
+        symbol_table: &mut SymbolTable,
+    ) -> Result<TokenTreeItem, CompileError> {
         let mut root = TokenTreeItem::new_root("parameterList");
 
         while let Some(next_token) = tokenizer.peek_next() {
@@ -394,31 +823,36 @@ impl SubroutineDec {
                 break;
             }
 
+            let cursor = tokenizer.get_cursor();
+
             if next_token.get_value() == "," {
-                root.push(tokenizer.consume(","));
+                root.push(tokenizer.consume(",")?);
             }
 
-            let parameter_type = tokenizer.retrieve_type();
-            let identifier = tokenizer.retrieve_identifier();
+            let parameter_type = retrieve_variable_type(tokenizer)?;
+            let identifier = retrieve_name(tokenizer)?;
 
-            symbol_table.add(
+            declare(
+                symbol_table,
                 "argument",
                 parameter_type.get_value().as_str(),
-                identifier.get_value().as_str(),
-            );
+                &identifier,
+            )?;
 
             root.push(parameter_type);
             root.push(identifier);
+
+            ensure_progress(tokenizer, cursor)?;
         }
 
-        root
+        Ok(root)
     }
 }
 
 pub struct Statement {}
 
 impl Statement {
-    pub fn build_list(tokenizer: &Tokenizer) -> TokenTreeItem {
+    pub fn build_list(tokenizer: &Tokenizer) -> Result<TokenTreeItem, CompileError> {
         let mut root = TokenTreeItem::new_root("statements");
 
         while let Some(next_token) = tokenizer.peek_next() {
@@ -426,21 +860,28 @@ impl Statement {
                 break;
             }
 
-            root.push_item(Statement::build(tokenizer));
+            let cursor = tokenizer.get_cursor();
+            root.push_item(Statement::build(tokenizer)?);
+            ensure_progress(tokenizer, cursor)?;
         }
 
-        root
+        Ok(root)
     }
 
-    pub fn build(tokenizer: &Tokenizer) -> TokenTreeItem {
-        let next_token = tokenizer.peek_next().unwrap();
+    pub fn build(tokenizer: &Tokenizer) -> Result<TokenTreeItem, CompileError> {
+        let next_token = tokenizer
+            .peek_next()
+            .ok_or_else(|| unexpected_eof(tokenizer, "statement"))?;
+
+        if let Some(extension) = tokenizer
+            .get_statement_registry()
+            .find_by_keyword(next_token.get_value().as_str())
+        {
+            return extension.build(tokenizer);
+        }
 
         if next_token.get_type() != TokenType::Keyword {
-            panic!(format!(
-                "Invalid token type on build of statement: {:?} ({})",
-                next_token.get_type(),
-                next_token.get_value()
-            ));
+            return Err(Statement::invalid_statement_error(next_token));
         }
 
         match next_token.get_value().as_str() {
@@ -449,158 +890,251 @@ impl Statement {
             "while" => Statement::build_while(tokenizer),
             "if" => Statement::build_if(tokenizer),
             "let" => Statement::build_let(tokenizer),
-            value => panic!(format!("Invalid statement value: {}", value)),
+            _ => Err(Statement::invalid_statement_error(next_token)),
         }
     }
 
-    pub fn build_return(tokenizer: &Tokenizer) -> TokenTreeItem {
+    /// Keywords without a built-in or registered statement land here too, so
+    /// a custom statement used without its extension reads as a plain error.
+    fn invalid_statement_error(token: &TokenItem) -> CompileError {
+        CompileError::syntax(
+            format!("expected a statement, found '{}'", token.get_value()).as_str(),
+            token.get_position(),
+        )
+    }
+
+    pub fn build_return(tokenizer: &Tokenizer) -> Result<TokenTreeItem, CompileError> {
         let mut root = TokenTreeItem::new_root("returnStatement");
 
-        root.push(tokenizer.consume("return"));
+        root.push(tokenizer.consume("return")?);
 
-        let next_token = tokenizer.peek_next().unwrap();
+        let next_token = tokenizer
+            .peek_next()
+            .ok_or_else(|| unexpected_eof(tokenizer, "return"))?;
 
         if next_token.get_value() == ";" {
-            root.push(tokenizer.consume(";"));
-            return root;
+            root.push(tokenizer.consume(";")?);
+            return Ok(root);
         }
 
-        root.push_item(Expression::build(tokenizer));
-        root.push(tokenizer.consume(";"));
+        root.push_item(Expression::build(tokenizer)?);
+        root.push(tokenizer.consume(";")?);
 
-        root
+        Ok(root)
     }
 
-    pub fn build_do(tokenizer: &Tokenizer) -> TokenTreeItem {
+    pub fn build_do(tokenizer: &Tokenizer) -> Result<TokenTreeItem, CompileError> {
         let mut root = TokenTreeItem::new_root("doStatement");
 
-        root.push(tokenizer.consume("do"));
+        root.push(tokenizer.consume("do")?);
+
+        root.push(retrieve_receiver(tokenizer)?);
 
-        root.push(tokenizer.retrieve_identifier());
-        SubroutineCall::build(&mut root, tokenizer);
+        if let Some(token) = tokenizer.peek_next() {
+            if token.get_value() == "=" {
+                return Err(CompileError::syntax(
+                    "unexpected '='; did you mean 'let'?",
+                    token.get_position(),
+                ));
+            }
+        }
 
-        root.push(tokenizer.consume(";"));
+        SubroutineCall::build(&mut root, tokenizer)?;
 
-        root
+        root.push(tokenizer.consume(";")?);
+
+        Ok(root)
     }
 
-    pub fn build_while(tokenizer: &Tokenizer) -> TokenTreeItem {
+    pub fn build_while(tokenizer: &Tokenizer) -> Result<TokenTreeItem, CompileError> {
         let mut root = TokenTreeItem::new_root("whileStatement");
 
-        root.push(tokenizer.consume("while"));
-        root.push(tokenizer.consume("("));
-        root.push_item(Expression::build(tokenizer));
-        root.push(tokenizer.consume(")"));
-        root.push(tokenizer.consume("{"));
-        root.push_item(Statement::build_list(tokenizer));
-        root.push(tokenizer.consume("}"));
+        root.push(tokenizer.consume("while")?);
+        root.push(tokenizer.consume("(")?);
+        root.push_item(Expression::build(tokenizer)?);
+        root.push(tokenizer.consume(")")?);
+        root.push(Statement::consume_body_brace(tokenizer, "while")?);
+        root.push_item(Statement::build_list(tokenizer)?);
+        root.push(tokenizer.consume("}")?);
 
-        root
+        Ok(root)
     }
 
-    pub fn build_if(tokenizer: &Tokenizer) -> TokenTreeItem {
+    pub fn build_if(tokenizer: &Tokenizer) -> Result<TokenTreeItem, CompileError> {
         let mut root = TokenTreeItem::new_root("ifStatement");
 
-        root.push(tokenizer.consume("if"));
-        root.push(tokenizer.consume("("));
-        root.push_item(Expression::build(tokenizer));
-        root.push(tokenizer.consume(")"));
-        root.push(tokenizer.consume("{"));
-        root.push_item(Statement::build_list(tokenizer));
-        root.push(tokenizer.consume("}"));
+        root.push(tokenizer.consume("if")?);
+        root.push(tokenizer.consume("(")?);
+        root.push_item(Expression::build(tokenizer)?);
+        root.push(tokenizer.consume(")")?);
+        root.push(Statement::consume_body_brace(tokenizer, "if")?);
+        root.push_item(Statement::build_list(tokenizer)?);
+        root.push(tokenizer.consume("}")?);
 
         let next_token = tokenizer.peek_next();
 
         if next_token.is_none() {
-            return root;
+            return Ok(root);
         }
 
         let next_token = next_token.unwrap();
 
         if next_token.get_value() == "else" {
-            root.push(tokenizer.consume("else"));
-            root.push(tokenizer.consume("{"));
-            root.push_item(Statement::build_list(tokenizer));
-            root.push(tokenizer.consume("}"));
+            root.push(tokenizer.consume("else")?);
+            root.push(Statement::consume_body_brace(tokenizer, "else")?);
+            root.push_item(Statement::build_list(tokenizer)?);
+            root.push(tokenizer.consume("}")?);
 
-            return root;
+            return Ok(root);
         }
 
-        root
+        Ok(root)
     }
 
-    pub fn build_let(tokenizer: &Tokenizer) -> TokenTreeItem {
+    /// Jack has no brace-less bodies: C habits like `if (x) do f();` are
+    /// reported instead of being accepted as a single-statement body, so the
+    /// language stays the one the course defines.
+    fn consume_body_brace(
+        tokenizer: &Tokenizer,
+        statement: &str,
+    ) -> Result<TokenItem, CompileError> {
+        match tokenizer.peek_next() {
+            Some(token) if token.get_value() == "{" => tokenizer.consume("{"),
+            Some(token) => Err(CompileError::syntax(
+                format!(
+                    "Jack requires braces around {} bodies, found '{}'",
+                    statement,
+                    token.get_value()
+                )
+                .as_str(),
+                token.get_position(),
+            )),
+            None => Err(unexpected_eof(tokenizer, statement)),
+        }
+    }
+
+    pub fn build_let(tokenizer: &Tokenizer) -> Result<TokenTreeItem, CompileError> {
         let mut root = TokenTreeItem::new_root("letStatement");
 
-        root.push(tokenizer.consume("let"));
-        root.push(tokenizer.retrieve_identifier());
+        root.push(tokenizer.consume("let")?);
+        root.push(retrieve_name(tokenizer)?);
 
-        let next_token = tokenizer.peek_next().unwrap();
+        let next_token = tokenizer
+            .peek_next()
+            .ok_or_else(|| unexpected_eof(tokenizer, "let"))?;
 
         if next_token.get_value() == "[" {
-            root.push(tokenizer.consume("["));
-            root.push_item(Expression::build(tokenizer));
-            root.push(tokenizer.consume("]"));
+            root.push(tokenizer.consume("[")?);
+            root.push_item(Expression::build(tokenizer)?);
+            root.push(tokenizer.consume("]")?);
         }
 
-        root.push(tokenizer.consume("="));
-        root.push_item(Expression::build(tokenizer));
-        root.push(tokenizer.consume(";"));
+        root.push(tokenizer.consume("=")?);
+        root.push_item(Expression::build(tokenizer)?);
+        root.push(tokenizer.consume(";")?);
 
-        root
+        Ok(root)
     }
 }
 
 pub struct Expression {}
 
 impl Expression {
-    pub fn build(tokenizer: &Tokenizer) -> TokenTreeItem {
+    pub fn build(tokenizer: &Tokenizer) -> Result<TokenTreeItem, CompileError> {
         let mut root = TokenTreeItem::new_root("expression");
 
-        root.push_item(Term::build(tokenizer));
+        root.push_item(Term::build(tokenizer)?);
 
         while let Some(next_token) = tokenizer.peek_next() {
             if !next_token.is_op() {
                 break;
             }
 
-            root.push(tokenizer.retrieve_op());
-            root.push_item(Term::build(tokenizer));
+            root.push(tokenizer.retrieve_op()?);
+            root.push_item(Term::build(tokenizer)?);
         }
 
-        root
+        Ok(root)
     }
 }
 
 struct SubroutineCall {}
 
 impl SubroutineCall {
-    pub fn build(root: &mut TokenTreeItem, tokenizer: &Tokenizer) {
-        let next_token = tokenizer.peek_next().unwrap();
+    pub fn build(root: &mut TokenTreeItem, tokenizer: &Tokenizer) -> Result<(), CompileError> {
+        let next_token = tokenizer
+            .peek_next()
+            .ok_or_else(|| unexpected_eof(tokenizer, "subroutine call"))?;
+
+        if next_token.get_value() == "[" {
+            let array = root
+                .get_nodes()
+                .last()
+                .unwrap()
+                .get_item()
+                .as_ref()
+                .unwrap();
+
+            return Err(SubroutineCall::array_element_call_error(array));
+        }
 
         if next_token.get_type() == TokenType::Symbol && next_token.get_value() == "(" {
-            root.push(tokenizer.consume("("));
-            root.push_item(SubroutineCall::build_expression_list(tokenizer));
-            root.push(tokenizer.consume(")"));
+            root.push(tokenizer.consume("(")?);
+            root.push_item(SubroutineCall::build_expression_list(tokenizer)?);
+            root.push(tokenizer.consume(")")?);
 
-            return;
+            return Ok(());
         }
 
         if next_token.get_type() == TokenType::Symbol && next_token.get_value() == "." {
-            root.push(tokenizer.consume("."));
-            root.push(tokenizer.retrieve_identifier());
+            root.push(tokenizer.consume(".")?);
+            root.push(retrieve_name(tokenizer)?);
+
+            let next_token = tokenizer
+                .peek_next()
+                .ok_or_else(|| unexpected_eof(tokenizer, "subroutine call"))?;
 
-            root.push(tokenizer.consume("("));
-            root.push_item(SubroutineCall::build_expression_list(tokenizer));
-            root.push(tokenizer.consume(")"));
+            if next_token.get_value() != "(" {
+                return Err(SubroutineCall::invalid_call_error("'('", next_token));
+            }
+
+            root.push(tokenizer.consume("(")?);
+            root.push_item(SubroutineCall::build_expression_list(tokenizer)?);
+            root.push(tokenizer.consume(")")?);
 
-            return;
+            return Ok(());
         }
 
-        panic!("Invalid next token on building subroutine call");
+        Err(SubroutineCall::invalid_call_error("'(' or '.'", next_token))
     }
 
-    fn build_expression_list(tokenizer: &Tokenizer) -> TokenTreeItem {
+    /// Jack arrays are untyped, so the class of `a[i]` is unknown at compile
+    /// time and `a[i].draw()` cannot be resolved to `Class.draw`. The element
+    /// has to be assigned to a typed variable before calling it.
+    fn array_element_call_error(array: &TokenItem) -> CompileError {
+        CompileError::syntax(
+            format!(
+                "cannot call a subroutine on an element of array '{}': its class is unknown, assign it to a typed variable first",
+                array.get_value()
+            )
+            .as_str(),
+            array.get_position(),
+        )
+    }
+
+    fn invalid_call_error(expected: &str, token: &TokenItem) -> CompileError {
+        CompileError::syntax(
+            format!(
+                "expected {} after identifier in subroutine call, found '{}'",
+                expected,
+                token.get_value()
+            )
+            .as_str(),
+            token.get_position(),
+        )
+    }
+
+    fn build_expression_list(tokenizer: &Tokenizer) -> Result<TokenTreeItem, CompileError> {
         let mut root = TokenTreeItem::new_root("expressionList");
 
         let next_token = tokenizer.peek_next();
@@ -609,109 +1143,241 @@ impl SubroutineCall {
             || next_token.unwrap().get_value() == ")"
             || next_token.unwrap().get_value() == "]"
         {
-            return root;
+            return Ok(root);
         }
 
-        root.push_item(Expression::build(tokenizer));
+        root.push_item(Expression::build(tokenizer)?);
 
         while let Some(next_token) = tokenizer.peek_next() {
             if next_token.get_type() != TokenType::Symbol || next_token.get_value() != "," {
                 break;
             }
 
-            root.push(tokenizer.consume(","));
-            root.push_item(Expression::build(tokenizer));
+            root.push(tokenizer.consume(",")?);
+            root.push_item(Expression::build(tokenizer)?);
         }
 
-        root
+        Ok(root)
     }
 }
 
 struct Term {}
 
 impl Term {
-    pub fn build(tokenizer: &Tokenizer) -> TokenTreeItem {
+    pub fn build(tokenizer: &Tokenizer) -> Result<TokenTreeItem, CompileError> {
         let mut root = TokenTreeItem::new_root("term");
 
-        let token = tokenizer.get_next().unwrap();
+        let token = tokenizer
+            .get_next()
+            .ok_or_else(|| unexpected_eof(tokenizer, "expression"))?;
         root.push(token.clone());
 
         match token.get_type() {
-            TokenType::Identifier => Term::build_identifier(&mut root, tokenizer),
-            TokenType::Symbol => {
-                Term::build_symbol(token.get_value().as_str(), &mut root, tokenizer)
+            TokenType::Identifier => Term::build_identifier(&mut root, tokenizer)?,
+            TokenType::Keyword
+                if token.get_value() == "this"
+                    && tokenizer
+                        .peek_next()
+                        .is_some_and(|next| next.get_value() == ".") =>
+            {
+                Term::build_identifier(&mut root, tokenizer)?
+            }
+            TokenType::Keyword if !KEYWORD_CONSTANTS.contains(&token.get_value().as_str()) => {
+                return Err(Term::not_an_expression_error(token))
             }
+            TokenType::Symbol => Term::build_symbol(token, &mut root, tokenizer)?,
             _ => (),
         };
 
-        root
+        Ok(root)
     }
 
-    fn build_identifier(root: &mut TokenTreeItem, tokenizer: &Tokenizer) {
+    fn build_identifier(
+        root: &mut TokenTreeItem,
+        tokenizer: &Tokenizer,
+    ) -> Result<(), CompileError> {
         let next_token = tokenizer.peek_next();
 
         if next_token.is_none() {
-            return;
+            return Ok(());
         }
 
         let next_token = next_token.unwrap();
 
         if next_token.get_value() == "[" {
-            root.push(tokenizer.consume("["));
-            root.push_item(Expression::build(tokenizer));
-            root.push(tokenizer.consume("]"));
+            root.push(tokenizer.consume("[")?);
+            root.push_item(Expression::build(tokenizer)?);
+            root.push(tokenizer.consume("]")?);
+
+            if tokenizer
+                .peek_next()
+                .is_some_and(|token| token.get_value() == ".")
+            {
+                let array = root
+                    .get_nodes()
+                    .first()
+                    .unwrap()
+                    .get_item()
+                    .as_ref()
+                    .unwrap();
+
+                return Err(SubroutineCall::array_element_call_error(array));
+            }
+
+            return Ok(());
+        }
 
-            return;
+        if next_token.get_value() == "." {
+            Term::ensure_not_field_access(root, tokenizer)?;
         }
 
         if [".", "("].contains(&next_token.get_value().as_str()) {
-            SubroutineCall::build(root, tokenizer);
+            SubroutineCall::build(root, tokenizer)?;
         }
+
+        Ok(())
     }
 
-    fn build_symbol(value: &str, root: &mut TokenTreeItem, tokenizer: &Tokenizer) {
+    /// `p.x` without a `(` reads like a field access, which Jack does not have.
+    fn ensure_not_field_access(
+        root: &TokenTreeItem,
+        tokenizer: &Tokenizer,
+    ) -> Result<(), CompileError> {
+        let member = match tokenizer.peek_at(1) {
+            Some(member) if member.get_type() == TokenType::Identifier => member,
+            _ => return Ok(()),
+        };
+
+        if tokenizer
+            .peek_at(2)
+            .is_some_and(|token| token.get_value() == "(")
+        {
+            return Ok(());
+        }
+
+        let object = root
+            .get_nodes()
+            .first()
+            .unwrap()
+            .get_item()
+            .as_ref()
+            .unwrap();
+
+        Err(CompileError::syntax(
+            format!(
+                "Jack has no direct field access ('{}.{}'); use an accessor method",
+                object.get_value(),
+                member.get_value()
+            )
+            .as_str(),
+            object.get_position(),
+        ))
+    }
+
+    fn build_symbol(
+        token: &TokenItem,
+        root: &mut TokenTreeItem,
+        tokenizer: &Tokenizer,
+    ) -> Result<(), CompileError> {
+        let value = token.get_value();
+
         if value == "(" {
-            root.push_item(Expression::build(tokenizer));
-            root.push(tokenizer.consume(")"));
+            root.push_item(Expression::build(tokenizer)?);
+            root.push(tokenizer.consume(")")?);
 
-            return;
+            return Ok(());
         }
 
-        if UNARY_OP_SYMBOLS.contains(&value) {
-            root.push_item(Term::build(tokenizer));
+        if UNARY_OP_SYMBOLS.contains(&value.as_str()) {
+            root.push_item(Term::build(tokenizer)?);
 
-            return;
+            return Ok(());
         }
 
-        panic!("Invalid symbol list inside an symbol call");
+        Err(Term::not_an_expression_error(token))
+    }
+
+    fn not_an_expression_error(token: &TokenItem) -> CompileError {
+        CompileError::syntax(
+            format!("expected an expression, found '{}'", token.get_value()).as_str(),
+            token.get_position(),
+        )
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::extension::{StatementExtension, StatementRegistry};
+    use crate::writer::VmWriter;
+    use std::rc::Rc;
 
     #[test]
     fn build_root_node() {
         let tokenizer = Tokenizer::new("class Test {}");
 
-        let result = ClassNode::build(&tokenizer);
+        let result = ClassNode::build(&tokenizer).unwrap();
 
         let name = result.get_name().as_ref();
         assert!(&name.is_some());
         assert_eq!(name.unwrap().as_str(), "class");
     }
 
+    #[test]
+    fn check_braces_missing_close() {
+        let tokenizer = Tokenizer::new("class Main {\n  function void main() {\n    return;\n}");
+
+        let result = ClassNode::build(&tokenizer);
+
+        assert_eq!(result.err().unwrap().to_string(), "1:12: unclosed '{'");
+    }
+
+    #[test]
+    fn check_braces_extra_close() {
+        let tokenizer = Tokenizer::new("class Main { function void main() { return; } } }");
+
+        let result = ClassNode::build(&tokenizer);
+
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "1:49: unexpected '}' without a matching '{'"
+        );
+    }
+
+    #[test]
+    fn check_braces_mismatch() {
+        let tokenizer = Tokenizer::new("let a[{i] = 1;");
+
+        let result = check_braces(tokenizer.get_tokens());
+
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "1:7: unclosed '{', found ']' at 1:9"
+        );
+    }
+
+    #[test]
+    fn build_statement_unknown_keyword() {
+        let tokenizer = Tokenizer::new("var int x;");
+
+        let result = Statement::build(&tokenizer);
+
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "1:1: expected a statement, found 'var'"
+        );
+    }
+
     #[test]
     fn build_class_var_dec_list() {
         let tokenizer = Tokenizer::new("field int x, y; static String name;");
         let mut symbol_table = SymbolTable::new();
 
-        let result = VarDec::build_class(&tokenizer, &mut symbol_table);
+        let result = VarDec::build_class(&tokenizer, &mut symbol_table).unwrap();
 
         assert_eq!(symbol_table.symbols.len(), 3);
 
-        let symbol = symbol_table.symbols.get(0).unwrap();
+        let symbol = symbol_table.symbols.first().unwrap();
         assert_eq!(symbol.name, "x");
         assert_eq!(symbol.symbol_type, SymbolType::Field);
         assert_eq!(symbol.kind, "int");
@@ -732,17 +1398,97 @@ mod tests {
         assert_eq!(result.len(), 2);
     }
 
+    #[test]
+    fn define_returns_segment_position() {
+        let mut symbol_table = SymbolTable::new();
+
+        assert_eq!(symbol_table.define("argument", "int", "a"), 0);
+        assert_eq!(symbol_table.define("var", "int", "x"), 0);
+        assert_eq!(symbol_table.define("argument", "int", "b"), 1);
+        assert_eq!(symbol_table.define("argument", "Array", "c"), 2);
+    }
+
+    #[test]
+    fn restore_symbol_table_snapshot() {
+        let mut symbol_table = SymbolTable::new();
+        symbol_table.add("field", "int", "x");
+        symbol_table.add("var", "int", "a");
+
+        let snapshot = symbol_table.snapshot();
+
+        symbol_table.add("field", "int", "y");
+        symbol_table.add("var", "boolean", "b");
+        symbol_table.add("argument", "int", "c");
+        symbol_table.restore(snapshot);
+
+        assert_eq!(symbol_table.symbols.len(), 2);
+        assert!(symbol_table.contains("x"));
+        assert!(!symbol_table.contains("y"));
+        assert!(!symbol_table.contains("b"));
+        assert!(!symbol_table.contains("c"));
+        assert_eq!(symbol_table.count_fields(), 1);
+
+        assert_eq!(symbol_table.define("var", "int", "b"), 1);
+        assert_eq!(symbol_table.define("argument", "int", "c"), 0);
+    }
+
+    #[test]
+    fn contains_and_get_type() {
+        let mut symbol_table = SymbolTable::new();
+        symbol_table.add("field", "Point", "origin");
+        symbol_table.add("var", "Array", "items");
+
+        assert!(symbol_table.contains("origin"));
+        assert!(symbol_table.contains("items"));
+        assert!(!symbol_table.contains("Point"));
+        assert!(!symbol_table.contains("missing"));
+
+        assert_eq!(symbol_table.get_type("origin"), "Point");
+        assert_eq!(symbol_table.get_type("items"), "Array");
+    }
+
+    #[test]
+    fn count_fields_skips_statics() {
+        let tokenizer = Tokenizer::new(
+            "class Point { field int x, y; static int count; field boolean visible; }",
+        );
+
+        let root = ClassNode::build(&tokenizer).unwrap();
+        let symbol_table = root.get_symbol_table().as_ref().unwrap();
+
+        assert_eq!(symbol_table.count_fields(), 3);
+        assert_eq!(symbol_table.static_count(), 1);
+    }
+
+    #[test]
+    fn build_class_var_dec_across_lines() {
+        let content = crate::builder::build_content(String::from("field int x,\r\n    y;"));
+        let tokenizer = Tokenizer::new(&content);
+        let mut symbol_table = SymbolTable::new();
+
+        let result = VarDec::build_class(&tokenizer, &mut symbol_table).unwrap();
+
+        assert_eq!(result.len(), 1);
+        assert_eq!(symbol_table.count_fields(), 2);
+
+        let symbol = symbol_table.symbols.get(1).unwrap();
+        assert_eq!(symbol.name, "y");
+        assert_eq!(symbol.symbol_type, SymbolType::Field);
+        assert_eq!(symbol.kind, "int");
+        assert_eq!(symbol.position, 1);
+    }
+
     #[test]
     fn build_subroutine_with_argumants_and_vars() {
         let tokenizer = Tokenizer::new("method void test(int x, String name) {var boolean a, b;}");
         let symbol_table = SymbolTable::new();
 
-        let result = SubroutineDec::build_subroutine(&tokenizer, &symbol_table);
+        let result = SubroutineDec::build_subroutine(&tokenizer, &symbol_table).unwrap();
         let symbol_table = result.symbol_table.as_ref().unwrap();
 
         assert_eq!(symbol_table.symbols.len(), 4);
 
-        let symbol = symbol_table.symbols.get(0).unwrap();
+        let symbol = symbol_table.symbols.first().unwrap();
         assert_eq!(symbol.name, "x");
         assert_eq!(symbol.symbol_type, SymbolType::Argument);
         assert_eq!(symbol.kind, "int");
@@ -777,11 +1523,11 @@ mod tests {
             Tokenizer::new("method void print(int x) {} function int count(String name) {}");
         let symbol_table = SymbolTable::new();
 
-        let result = SubroutineDec::build(&tokenizer, &symbol_table);
+        let result = SubroutineDec::build(&tokenizer, &symbol_table).unwrap();
 
         assert_eq!(result.len(), 2);
 
-        let subroutine = result.get(0).unwrap();
+        let subroutine = result.first().unwrap();
         let identifier = subroutine.nodes.get(2).unwrap();
         assert_eq!(identifier.get_item().as_ref().unwrap().get_value(), "print");
 
@@ -790,6 +1536,375 @@ mod tests {
         assert_eq!(identifier.get_item().as_ref().unwrap().get_value(), "count");
     }
 
+    #[test]
+    fn structurally_eq_ignores_layout() {
+        let source =
+            "class Point { field int x; method int get() { if (x > 0) { return x; } return 0; } }";
+        let formatted = "class Point {\n    field int x;\n\n    method int get() {\n        if (x > 0) {\n            return x;\n        }\n        return 0;\n    }\n}\n";
+
+        let tree = ClassNode::build(&Tokenizer::new(source)).unwrap();
+        let formatted_tree = ClassNode::build(&Tokenizer::new(formatted)).unwrap();
+
+        assert!(tree.structurally_eq(&formatted_tree));
+    }
+
+    #[test]
+    fn structurally_eq_detects_differences() {
+        let tree = Statement::build(&Tokenizer::new("let x = a + 1;")).unwrap();
+        let another_value = Statement::build(&Tokenizer::new("let x = a + 2;")).unwrap();
+        let another_shape = Statement::build(&Tokenizer::new("let x = a;")).unwrap();
+
+        assert!(!tree.structurally_eq(&another_value));
+        assert!(!tree.structurally_eq(&another_shape));
+    }
+
+    #[test]
+    fn build_recovering_collects_errors() {
+        let source = "class Main { function void a() { do x; return; } function void b() { return; } method void c() { do y.z; return; } }";
+        let tokenizer = Tokenizer::new(source);
+        let mut diagnostics = Diagnostics::new(20);
+
+        let result = ClassNode::build_recovering(&tokenizer, &mut diagnostics);
+
+        assert!(result.is_none());
+        assert_eq!(diagnostics.get_errors().len(), 2);
+        assert_eq!(
+            diagnostics.get_errors().get(1).unwrap().to_string(),
+            "1:104: expected '(' after identifier in subroutine call, found ';'"
+        );
+    }
+
+    #[test]
+    fn build_recovering_honors_max_errors() {
+        let mut source = String::from("class Main {");
+        for i in 0..30 {
+            source.push_str(format!(" function void f{}() {{ do x; return; }}", i).as_str());
+        }
+        source.push('}');
+
+        let tokenizer = Tokenizer::new(source.as_str());
+        let mut diagnostics = Diagnostics::new(5);
+
+        let result = ClassNode::build_recovering(&tokenizer, &mut diagnostics);
+
+        assert!(result.is_none());
+        assert_eq!(diagnostics.get_errors().len(), 5);
+        assert_eq!(
+            diagnostics.render("Main.jack").last().unwrap(),
+            "Main.jack: too many errors, stopped after 5"
+        );
+    }
+
+    #[test]
+    fn build_recovering_without_errors() {
+        let tokenizer = Tokenizer::new("class Main { function void a() { return; } }");
+        let mut diagnostics = Diagnostics::new(20);
+
+        let result = ClassNode::build_recovering(&tokenizer, &mut diagnostics);
+
+        assert!(result.is_some());
+        assert!(!diagnostics.has_errors());
+    }
+
+    struct StalledStatement {}
+
+    impl StatementExtension for StalledStatement {
+        fn keyword(&self) -> &str {
+            "stall"
+        }
+
+        fn build(&self, _tokenizer: &Tokenizer) -> Result<TokenTreeItem, CompileError> {
+            Ok(TokenTreeItem::new_root("stallStatement"))
+        }
+
+        fn write(&self, _writer: &mut VmWriter, _tree: &TokenTreeItem) -> Vec<String> {
+            Vec::new()
+        }
+    }
+
+    #[test]
+    fn build_statement_list_without_progress() {
+        let mut registry = StatementRegistry::new();
+        registry.register(Rc::new(StalledStatement {}));
+
+        let mut tokenizer = Tokenizer::new("stall; return;");
+        tokenizer.set_statement_registry(registry);
+
+        let result = Statement::build_list(&tokenizer);
+
+        assert_eq!(
+            result.err(),
+            Some(CompileError::NoProgress {
+                position: Position::new(1, 1)
+            })
+        );
+    }
+
+    #[test]
+    fn build_do_without_parenthesis() {
+        let tokenizer = Tokenizer::new("do obj.run;");
+
+        let result = Statement::build(&tokenizer);
+
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "1:11: expected '(' after identifier in subroutine call, found ';'"
+        );
+    }
+
+    #[test]
+    fn build_var_with_void_type() {
+        let tokenizer =
+            Tokenizer::new("class Main { function void main() { var void x; return; } }");
+
+        let result = ClassNode::build(&tokenizer);
+
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "1:41: 'void' is not a valid variable type"
+        );
+    }
+
+    #[test]
+    fn build_let_with_keyword_as_name() {
+        let tokenizer = Tokenizer::new("let return = 1;");
+
+        let result = Statement::build(&tokenizer);
+
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "1:5: 'return' is a reserved keyword and cannot be used as an identifier"
+        );
+    }
+
+    #[test]
+    fn build_field_with_keyword_as_name() {
+        let tokenizer = Tokenizer::new("class Main { field int if; }");
+
+        let result = ClassNode::build(&tokenizer);
+
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "1:24: 'if' is a reserved keyword and cannot be used as an identifier"
+        );
+    }
+
+    #[test]
+    fn build_class_with_duplicate_subroutines() {
+        let tokenizer = Tokenizer::new(
+            "class Main { function void f() { return; } function void f() { return; } }",
+        );
+
+        let result = ClassNode::build(&tokenizer);
+
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "1:58: duplicate subroutine 'f', first declared at 1:28"
+        );
+    }
+
+    #[test]
+    fn build_if_without_braces() {
+        let tokenizer = Tokenizer::new("if (x) do f();");
+
+        let result = Statement::build(&tokenizer);
+
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "1:8: Jack requires braces around if bodies, found 'do'"
+        );
+    }
+
+    #[test]
+    fn build_do_with_assignment() {
+        let tokenizer = Tokenizer::new("do x = 5;");
+
+        let result = Statement::build(&tokenizer);
+
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "1:6: unexpected '='; did you mean 'let'?"
+        );
+    }
+
+    #[test]
+    fn build_function_returning_this() {
+        let tokenizer = Tokenizer::new("class Main { function void f() { return this; } }");
+
+        let result = ClassNode::build(&tokenizer);
+
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "1:41: 'this' is not available in a function"
+        );
+    }
+
+    #[test]
+    fn build_let_truncated() {
+        let tokenizer = Tokenizer::new("let x =");
+
+        let result = Statement::build(&tokenizer);
+
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "1:7: unexpected end of file while parsing expression"
+        );
+    }
+
+    #[test]
+    fn build_let_missing_expression() {
+        let tokenizer = Tokenizer::new("let x = ;");
+
+        let result = Statement::build(&tokenizer);
+
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "1:9: expected an expression, found ';'"
+        );
+    }
+
+    #[test]
+    fn build_let_missing_left_operand() {
+        let tokenizer = Tokenizer::new("let x = * 2;");
+
+        let result = Statement::build(&tokenizer);
+
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "1:9: expected an expression, found '*'"
+        );
+    }
+
+    #[test]
+    fn build_let_keyword_as_expression() {
+        let tokenizer = Tokenizer::new("let x = int;");
+
+        let result = Statement::build(&tokenizer);
+
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "1:9: expected an expression, found 'int'"
+        );
+    }
+
+    #[test]
+    fn build_return_missing_right_operand() {
+        let tokenizer = Tokenizer::new("return 1 + ;");
+
+        let result = Statement::build(&tokenizer);
+
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "1:12: expected an expression, found ';'"
+        );
+    }
+
+    #[test]
+    fn build_statements_truncated() {
+        let cases = [
+            ("return", "1:1: unexpected end of file while parsing return"),
+            ("let x", "1:5: unexpected end of file while parsing let"),
+            (
+                "do f",
+                "1:4: unexpected end of file while parsing subroutine call",
+            ),
+            (
+                "do Output.printInt",
+                "1:11: unexpected end of file while parsing subroutine call",
+            ),
+        ];
+
+        for (source, message) in cases.iter() {
+            let result = Statement::build(&Tokenizer::new(source));
+
+            assert_eq!(result.err().unwrap().to_string(), *message);
+        }
+    }
+
+    #[test]
+    fn build_return_truncated() {
+        let tokenizer = Tokenizer::new("return 1 +");
+
+        let result = Statement::build(&tokenizer);
+
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "1:10: unexpected end of file while parsing expression"
+        );
+    }
+
+    #[test]
+    fn build_expression_with_missing_close_paren() {
+        let tokenizer = Tokenizer::new("class Main { function int main() { return ( (a + b ); } }");
+
+        let result = ClassNode::build(&tokenizer);
+
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "1:43: unbalanced parentheses: expected ')'"
+        );
+    }
+
+    #[test]
+    fn build_expression_with_extra_close_paren() {
+        let tokenizer = Tokenizer::new("class Main { function int main() { return a + b ); } }");
+
+        let result = ClassNode::build(&tokenizer);
+
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "1:49: unbalanced parentheses: unexpected ')'"
+        );
+    }
+
+    #[test]
+    fn build_do_on_array_element() {
+        let tokenizer = Tokenizer::new("do a[i].draw();");
+
+        let result = Statement::build(&tokenizer);
+
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "1:4: cannot call a subroutine on an element of array 'a': its class is unknown, assign it to a typed variable first"
+        );
+    }
+
+    #[test]
+    fn build_let_with_array_element_call() {
+        let tokenizer = Tokenizer::new("let x = a[i].area();");
+
+        let result = Statement::build(&tokenizer);
+
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "1:9: cannot call a subroutine on an element of array 'a': its class is unknown, assign it to a typed variable first"
+        );
+    }
+
+    #[test]
+    fn build_let_with_field_access() {
+        let tokenizer = Tokenizer::new("let d = p.x;");
+
+        let result = Statement::build(&tokenizer);
+
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "1:9: Jack has no direct field access ('p.x'); use an accessor method"
+        );
+    }
+
+    #[test]
+    fn build_do_without_call() {
+        let tokenizer = Tokenizer::new("do obj;");
+
+        let result = Statement::build(&tokenizer);
+
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "1:7: expected '(' or '.' after identifier in subroutine call, found ';'"
+        );
+    }
+
     //     #[test]
     //     fn build_term_integer() {
     //         let tokenizer = Tokenizer::new("123");
@@ -0,0 +1,137 @@
+use std::collections::BTreeSet;
+
+use crate::{
+    parser::TokenTreeItem,
+    tokenizer::{TokenItem, TokenType},
+};
+
+/// The classes of the Jack OS, always available to a program.
+pub const OS_CLASSES: [&str; 8] = [
+    "Array", "Keyboard", "Math", "Memory", "Output", "Screen", "String", "Sys",
+];
+
+const PRIMITIVE_TYPES: [&str; 4] = ["int", "char", "boolean", "void"];
+
+/// The classes a `class` tree depends on: the types of its variables,
+/// parameters and return values, and the receivers of `Class.sub()` calls
+/// that are not variables.
+pub fn referenced_classes(root: &TokenTreeItem) -> BTreeSet<String> {
+    let mut result = BTreeSet::new();
+
+    let mut add_kinds = |tree: &TokenTreeItem| {
+        if let Some(symbol_table) = tree.get_symbol_table() {
+            for symbol in symbol_table.iter() {
+                if !PRIMITIVE_TYPES.contains(&symbol.get_kind().as_str()) {
+                    result.insert(symbol.get_kind());
+                }
+            }
+        }
+    };
+
+    add_kinds(root);
+
+    for subroutine in root.get_nodes() {
+        add_kinds(subroutine);
+    }
+
+    for subroutine in root.get_nodes() {
+        let symbol_table = match subroutine.get_symbol_table() {
+            Some(symbol_table) => symbol_table,
+            None => continue,
+        };
+
+        if let Some(return_type) = subroutine
+            .get_nodes()
+            .get(1)
+            .and_then(|node| node.get_item().as_ref())
+            .filter(|item| item.get_type() == TokenType::Identifier)
+        {
+            result.insert(return_type.get_value());
+        }
+
+        let mut receivers = Vec::new();
+        collect_receivers(subroutine, &mut receivers);
+
+        for receiver in receivers {
+            if !symbol_table.contains(&receiver.get_value()) {
+                result.insert(receiver.get_value());
+            }
+        }
+    }
+
+    result
+}
+
+/// Identifiers followed by a `.`, like `Helper` in `Helper.run()`.
+fn collect_receivers<'a>(tree: &'a TokenTreeItem, receivers: &mut Vec<&'a TokenItem>) {
+    let nodes = tree.get_nodes();
+
+    for (i, node) in nodes.iter().enumerate() {
+        if let Some(item) = node.get_item() {
+            let is_receiver = item.get_type() == TokenType::Identifier
+                && nodes
+                    .get(i + 1)
+                    .and_then(|next| next.get_item().as_ref())
+                    .is_some_and(|next| next.get_value() == ".");
+
+            if is_receiver {
+                receivers.push(item);
+            }
+        }
+
+        collect_receivers(node, receivers);
+    }
+}
+
+/// The classes referenced by `roots` that are neither one of them nor an OS
+/// class, in alphabetical order.
+pub fn unresolved_classes(roots: &[TokenTreeItem]) -> Vec<String> {
+    let defined: BTreeSet<String> = roots
+        .iter()
+        .filter_map(|root| root.get_nodes().get(1)?.get_item().as_ref())
+        .map(|item| item.get_value())
+        .chain(OS_CLASSES.iter().map(|name| String::from(*name)))
+        .collect();
+
+    roots
+        .iter()
+        .flat_map(referenced_classes)
+        .collect::<BTreeSet<String>>()
+        .difference(&defined)
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parser::ClassNode, tokenizer::Tokenizer};
+
+    fn parse(source: &str) -> TokenTreeItem {
+        ClassNode::build(&Tokenizer::new(source)).unwrap()
+    }
+
+    #[test]
+    fn referenced_classes_of_a_class() {
+        let root = parse(
+            "class Main { field Point p; function Shape make(Line l) { var Array a; do p.draw(); do Helper.run(); do Output.printInt(1); return null; } }",
+        );
+
+        let classes: Vec<String> = referenced_classes(&root).into_iter().collect();
+
+        assert_eq!(
+            classes,
+            vec!["Array", "Helper", "Line", "Output", "Point", "Shape"]
+        );
+    }
+
+    #[test]
+    fn unresolved_helper_class() {
+        let roots = vec![
+            parse("class Main { function void main() { var Other o; let o = Other.new(); do Helper.run(o); do Output.println(); return; } }"),
+            parse("class Other { constructor Other new() { return this; } }"),
+        ];
+
+        assert_eq!(unresolved_classes(&roots), vec!["Helper"]);
+    }
+}
@@ -0,0 +1,97 @@
+use std::io::{self, BufRead, Write};
+
+use crate::{compiler::compile_statement, options::Options, parser::SymbolTable, writer::VmWriter};
+
+/// Reads one snippet per line from `input` and writes its VM code to
+/// `output`. `var` declarations are kept for the following lines; a line that
+/// fails to compile is reported and its declarations are dropped.
+pub fn run_repl<R: BufRead, W: Write>(
+    input: R,
+    mut output: W,
+    options: &Options,
+) -> io::Result<()> {
+    let mut symbol_table = SymbolTable::new();
+    let mut writer = VmWriter::with_options(options.writer_options.clone());
+
+    for line in input.lines() {
+        let line = line?;
+
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let snapshot = symbol_table.snapshot();
+
+        match compile_statement(&line, &mut symbol_table, &mut writer) {
+            Ok(code) => {
+                for vm_line in code {
+                    writeln!(output, "{}", vm_line)?;
+                }
+            }
+            Err(error) => {
+                symbol_table.restore(snapshot);
+                writeln!(output, "error: {}", error)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repl_keeps_declarations_across_lines() {
+        let options =
+            Options::parse(&[String::from("jack_compiler"), String::from("--repl")]).unwrap();
+        let input = "var int x;\nlet x = 2 + 3;\n\nx * 2\nlet y = 1;\nx\n";
+        let mut output = Vec::new();
+
+        run_repl(input.as_bytes(), &mut output, &options).unwrap();
+
+        assert_eq!(
+            String::from_utf8(output)
+                .unwrap()
+                .lines()
+                .collect::<Vec<&str>>(),
+            vec![
+                "push constant 2",
+                "push constant 3",
+                "add",
+                "pop local 0",
+                "push local 0",
+                "push constant 2",
+                "call Math.multiply 2",
+                "error: 1:5: 'y' is not declared",
+                "push local 0",
+            ]
+        );
+    }
+
+    #[test]
+    fn repl_reports_truncated_lines_and_keeps_reading() {
+        let options =
+            Options::parse(&[String::from("jack_compiler"), String::from("--repl")]).unwrap();
+        let input = "var int x;\nreturn\nlet x\ndo f\ndo Output.printInt\nlet x = 1;\n";
+        let mut output = Vec::new();
+
+        run_repl(input.as_bytes(), &mut output, &options).unwrap();
+
+        assert_eq!(
+            String::from_utf8(output)
+                .unwrap()
+                .lines()
+                .collect::<Vec<&str>>(),
+            vec![
+                "error: 1:1: unexpected end of file while parsing return",
+                "error: 1:5: unexpected end of file while parsing let",
+                "error: 1:4: unexpected end of file while parsing subroutine call",
+                "error: 1:11: unexpected end of file while parsing subroutine call",
+                "push constant 1",
+                "pop local 0",
+            ]
+        );
+    }
+}
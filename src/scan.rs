@@ -0,0 +1,150 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The `.jack` files found under a folder, plus the entries that could not
+/// be read. A broken entry is skipped instead of aborting the whole scan.
+pub struct Scan {
+    files: Vec<PathBuf>,
+    errors: Vec<String>,
+}
+
+impl Scan {
+    pub fn get_files(&self) -> &Vec<PathBuf> {
+        &self.files
+    }
+
+    pub fn get_errors(&self) -> &Vec<String> {
+        &self.errors
+    }
+}
+
+/// Lists the `.jack` files in `folder`, going down at most `max_depth`
+/// levels of sub folders (0 only looks at `folder` itself). Folders already
+/// visited, like the target of a symlink cycle, are skipped.
+pub fn find_jack_files(folder: &Path, max_depth: usize) -> Scan {
+    let mut scan = Scan {
+        files: Vec::new(),
+        errors: Vec::new(),
+    };
+    let mut visited = HashSet::new();
+
+    scan_folder(folder, max_depth, &mut visited, &mut scan);
+
+    scan
+}
+
+fn scan_folder(folder: &Path, depth: usize, visited: &mut HashSet<PathBuf>, scan: &mut Scan) {
+    match fs::canonicalize(folder) {
+        Ok(real_path) => {
+            if !visited.insert(real_path) {
+                return;
+            }
+        }
+        Err(error) => {
+            scan.errors.push(format!("{}: {}", folder.display(), error));
+            return;
+        }
+    }
+
+    let entries = match fs::read_dir(folder) {
+        Ok(entries) => entries,
+        Err(error) => {
+            scan.errors.push(format!("{}: {}", folder.display(), error));
+            return;
+        }
+    };
+
+    let mut paths = Vec::new();
+
+    for entry in entries {
+        match entry {
+            Ok(entry) => paths.push(entry.path()),
+            Err(error) => scan.errors.push(format!("{}: {}", folder.display(), error)),
+        }
+    }
+
+    paths.sort();
+
+    for path in paths {
+        let metadata = match fs::metadata(&path) {
+            Ok(metadata) => metadata,
+            Err(error) => {
+                scan.errors.push(format!("{}: {}", path.display(), error));
+                continue;
+            }
+        };
+
+        if metadata.is_dir() {
+            if depth > 0 {
+                scan_folder(&path, depth - 1, visited, scan);
+            }
+        } else if path
+            .extension()
+            .is_some_and(|extension| extension == "jack")
+        {
+            scan.files.push(path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    fn create_folder(name: &str) -> PathBuf {
+        let folder = env::temp_dir().join(format!("jack_scan_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&folder);
+        fs::create_dir_all(&folder).unwrap();
+
+        folder
+    }
+
+    #[test]
+    fn scan_respects_max_depth() {
+        let folder = create_folder("depth");
+        fs::create_dir_all(folder.join("sub")).unwrap();
+        fs::write(folder.join("Main.jack"), "").unwrap();
+        fs::write(folder.join("Main.vm"), "").unwrap();
+        fs::write(folder.join("sub").join("Other.jack"), "").unwrap();
+
+        let scan = find_jack_files(&folder, 0);
+        assert_eq!(scan.get_files(), &vec![folder.join("Main.jack")]);
+
+        let scan = find_jack_files(&folder, 1);
+        assert_eq!(
+            scan.get_files(),
+            &vec![
+                folder.join("Main.jack"),
+                folder.join("sub").join("Other.jack")
+            ]
+        );
+
+        fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn scan_skips_broken_entries_and_cycles() {
+        use std::os::unix::fs::symlink;
+
+        let folder = create_folder("broken");
+        fs::create_dir_all(folder.join("sub")).unwrap();
+        fs::write(folder.join("A.jack"), "").unwrap();
+        fs::write(folder.join("sub").join("B.jack"), "").unwrap();
+        symlink(folder.join("missing"), folder.join("broken")).unwrap();
+        symlink(&folder, folder.join("sub").join("loop")).unwrap();
+
+        let scan = find_jack_files(&folder, 10);
+
+        assert_eq!(
+            scan.get_files(),
+            &vec![folder.join("A.jack"), folder.join("sub").join("B.jack")]
+        );
+        assert_eq!(scan.get_errors().len(), 1);
+        assert!(scan.get_errors().first().unwrap().contains("broken"));
+
+        fs::remove_dir_all(&folder).unwrap();
+    }
+}
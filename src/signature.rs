@@ -0,0 +1,118 @@
+use crate::{parser::TokenTreeItem, tokenizer::Position};
+
+/// The declaration line of a subroutine: `method void draw(int x, int y)`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SubroutineSig {
+    kind: String,
+    return_type: String,
+    name: String,
+    parameters: Vec<(String, String)>,
+    position: Position,
+}
+
+impl SubroutineSig {
+    /// `constructor`, `function` or `method`.
+    pub fn get_kind(&self) -> &String {
+        &self.kind
+    }
+
+    pub fn get_return_type(&self) -> &String {
+        &self.return_type
+    }
+
+    pub fn get_name(&self) -> &String {
+        &self.name
+    }
+
+    pub fn get_parameter_types(&self) -> Vec<&String> {
+        self.parameters.iter().map(|(kind, _)| kind).collect()
+    }
+
+    pub fn get_parameter_names(&self) -> Vec<&String> {
+        self.parameters.iter().map(|(_, name)| name).collect()
+    }
+
+    /// Where the subroutine name appears.
+    pub fn get_position(&self) -> Position {
+        self.position
+    }
+}
+
+/// Lists the subroutines declared on a `class` tree, in source order.
+pub fn subroutine_signatures(root: &TokenTreeItem) -> Vec<SubroutineSig> {
+    root.get_nodes()
+        .iter()
+        .filter(|node| node.get_name().as_deref() == Some("subroutineDec"))
+        .map(build_signature)
+        .collect()
+}
+
+fn build_signature(tree: &TokenTreeItem) -> SubroutineSig {
+    let item = |index: usize| {
+        tree.get_nodes()
+            .get(index)
+            .unwrap()
+            .get_item()
+            .as_ref()
+            .unwrap()
+    };
+    let value = |index: usize| item(index).get_value();
+
+    let parameter_tokens: Vec<String> = tree
+        .get_nodes()
+        .get(4)
+        .unwrap()
+        .get_nodes()
+        .iter()
+        .filter_map(|node| node.get_item().as_ref())
+        .map(|item| item.get_value())
+        .filter(|value| value != ",")
+        .collect();
+
+    SubroutineSig {
+        kind: value(0),
+        return_type: value(1),
+        name: value(2),
+        parameters: parameter_tokens
+            .chunks(2)
+            .map(|pair| (pair[0].clone(), pair[1].clone()))
+            .collect(),
+        position: item(2).get_position(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parser::ClassNode, tokenizer::Tokenizer};
+
+    #[test]
+    fn signatures_of_each_kind() {
+        let source = "class Point { field int x, y; constructor Point new(int ax, int ay) { return this; } function int distance(Point a, Point b) { return 0; } method void draw() { return; } }";
+        let tokenizer = Tokenizer::new(source);
+        let tree = ClassNode::build(&tokenizer).unwrap();
+
+        let signatures = subroutine_signatures(&tree);
+
+        assert_eq!(signatures.len(), 3);
+
+        let constructor = signatures.first().unwrap();
+        assert_eq!(constructor.get_kind(), "constructor");
+        assert_eq!(constructor.get_return_type(), "Point");
+        assert_eq!(constructor.get_name(), "new");
+        assert_eq!(constructor.get_parameter_types(), vec!["int", "int"]);
+        assert_eq!(constructor.get_parameter_names(), vec!["ax", "ay"]);
+
+        let function = signatures.get(1).unwrap();
+        assert_eq!(function.get_kind(), "function");
+        assert_eq!(function.get_return_type(), "int");
+        assert_eq!(function.get_name(), "distance");
+        assert_eq!(function.get_parameter_types(), vec!["Point", "Point"]);
+
+        let method = signatures.get(2).unwrap();
+        assert_eq!(method.get_kind(), "method");
+        assert_eq!(method.get_return_type(), "void");
+        assert_eq!(method.get_name(), "draw");
+        assert!(method.get_parameter_types().is_empty());
+    }
+}
@@ -0,0 +1,104 @@
+use crate::{lint::Warning, parser::TokenTreeItem};
+
+/// What a compiled class is made of, for build dashboards.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClassSummary {
+    name: String,
+    fields: usize,
+    statics: usize,
+    subroutines: usize,
+    vm_lines: usize,
+    warnings: Vec<String>,
+}
+
+impl ClassSummary {
+    pub fn new(root: &TokenTreeItem, code: &[String], warnings: &[Warning]) -> ClassSummary {
+        let name = root
+            .get_nodes()
+            .get(1)
+            .and_then(|node| node.get_item().as_ref())
+            .map(|item| item.get_value())
+            .unwrap_or_default();
+        let (fields, statics) = match root.get_symbol_table() {
+            Some(symbol_table) => (symbol_table.count_fields(), symbol_table.static_count()),
+            None => (0, 0),
+        };
+        let subroutines = root
+            .get_nodes()
+            .iter()
+            .filter(|node| node.get_name().as_deref() == Some("subroutineDec"))
+            .count();
+
+        ClassSummary {
+            name,
+            fields,
+            statics,
+            subroutines,
+            vm_lines: code.len(),
+            warnings: warnings.iter().map(|warning| warning.to_string()).collect(),
+        }
+    }
+
+    pub fn get_name(&self) -> &String {
+        &self.name
+    }
+
+    /// The summary as a single line JSON object.
+    pub fn to_json(&self) -> String {
+        let warnings: Vec<String> = self.warnings.iter().map(|w| json_string(w)).collect();
+
+        format!(
+            "{{\"class\":{},\"fields\":{},\"statics\":{},\"subroutines\":{},\"vm_lines\":{},\"warnings\":[{}]}}",
+            json_string(&self.name),
+            self.fields,
+            self.statics,
+            self.subroutines,
+            self.vm_lines,
+            warnings.join(",")
+        )
+    }
+}
+
+fn json_string(value: &str) -> String {
+    let mut result = String::from("\"");
+
+    for c in value.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            c if (c as u32) < 0x20 => result.push_str(&format!("\\u{:04x}", c as u32)),
+            c => result.push(c),
+        }
+    }
+
+    result.push('"');
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parser::ClassNode, tokenizer::Position, tokenizer::Tokenizer, writer::VmWriter};
+
+    #[test]
+    fn summary_of_point() {
+        let tokenizer = Tokenizer::new(
+            "class Point { field int x, y; static int count; constructor Point new() { return this; } method int getX() { return x; } }",
+        );
+        let root = ClassNode::build(&tokenizer).unwrap();
+        let code = VmWriter::new().build(&root);
+        let warnings = vec![Warning::new("say \"hi\"", Position::new(1, 2))];
+
+        let summary = ClassSummary::new(&root, &code, &warnings);
+
+        assert_eq!(summary.get_name(), "Point");
+        assert_eq!(
+            summary.to_json(),
+            "{\"class\":\"Point\",\"fields\":2,\"statics\":1,\"subroutines\":2,\"vm_lines\":11,\"warnings\":[\"1:2: say \\\"hi\\\"\"]}"
+        );
+    }
+}
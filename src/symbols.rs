@@ -0,0 +1,134 @@
+use crate::{output::OutputSink, parser::TokenTreeItem};
+
+/// Lists the symbols of a `class` tree as CSV: class variables first, scoped
+/// to `class`, then the arguments and locals of each subroutine, scoped to
+/// `Class.sub`. Indexes are the ones used by the VM, so method arguments
+/// start at 1.
+pub fn symbols_csv(root: &TokenTreeItem) -> Vec<String> {
+    let mut result = vec![String::from("name,segment,index,kind,scope")];
+
+    let value = |tree: &TokenTreeItem, index: usize| {
+        tree.get_nodes()
+            .get(index)
+            .and_then(|node| node.get_item().as_ref())
+            .map(|item| item.get_value())
+            .unwrap_or_default()
+    };
+    let class_name = value(root, 1);
+
+    if let Some(symbol_table) = root.get_symbol_table() {
+        for symbol in symbol_table.iter() {
+            result.push(csv_row(&[
+                symbol.get_name(),
+                &symbol.get_type_as_str(),
+                &symbol.get_position().to_string(),
+                &symbol.get_kind(),
+                "class",
+            ]));
+        }
+    }
+
+    for subroutine in root.get_nodes() {
+        let symbol_table = match subroutine.get_symbol_table() {
+            Some(symbol_table) => symbol_table,
+            None => continue,
+        };
+
+        let scope = format!("{}.{}", class_name, value(subroutine, 2));
+        let argument_offset = if value(subroutine, 0) == "method" {
+            1
+        } else {
+            0
+        };
+
+        for symbol in symbol_table.iter() {
+            let index = match symbol.get_type_as_str().as_str() {
+                "argument" => symbol.get_position() + argument_offset,
+                "local" => symbol.get_position(),
+                _ => continue,
+            };
+
+            result.push(csv_row(&[
+                symbol.get_name(),
+                &symbol.get_type_as_str(),
+                &index.to_string(),
+                &symbol.get_kind(),
+                &scope,
+            ]));
+        }
+    }
+
+    result
+}
+
+/// Writes `symbols_csv` to `<Class>.csv` next to the source file.
+pub fn write_symbols_csv(filename: &str, root: &TokenTreeItem, sink: &mut dyn OutputSink) {
+    sink.write(
+        filename.replace(".jack", ".csv").as_str(),
+        symbols_csv(root).join("\r\n").as_str(),
+    );
+}
+
+fn csv_row(values: &[&str]) -> String {
+    values
+        .iter()
+        .map(|value| csv_field(value))
+        .collect::<Vec<String>>()
+        .join(",")
+}
+
+/// Quotes a field holding a comma, a quote or a line break, doubling its quotes.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        String::from(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{output::MemorySink, parser::ClassNode, tokenizer::Tokenizer};
+
+    #[test]
+    fn csv_rows_for_fields_and_locals() {
+        let source = "class Point { field int x, y; static Point origin; method int distance(Point other) { var int dx, dy; return 0; } function void reset() { return; } }";
+        let tokenizer = Tokenizer::new(source);
+        let tree = ClassNode::build(&tokenizer).unwrap();
+
+        assert_eq!(
+            symbols_csv(&tree),
+            vec![
+                "name,segment,index,kind,scope",
+                "x,this,0,int,class",
+                "y,this,1,int,class",
+                "origin,static,0,Point,class",
+                "other,argument,1,Point,Point.distance",
+                "dx,local,0,int,Point.distance",
+                "dy,local,1,int,Point.distance",
+            ]
+        );
+    }
+
+    #[test]
+    fn write_symbols_csv_to_sink() {
+        let tokenizer = Tokenizer::new("class Main { static int count; }");
+        let tree = ClassNode::build(&tokenizer).unwrap();
+        let mut sink = MemorySink::new();
+
+        write_symbols_csv("src/Main.jack", &tree, &mut sink);
+
+        assert_eq!(
+            sink.get("src/Main.csv").unwrap(),
+            "name,segment,index,kind,scope\r\ncount,static,0,int,class"
+        );
+    }
+
+    #[test]
+    fn csv_field_escaping() {
+        assert_eq!(csv_field("int"), "int");
+        assert_eq!(csv_field("Map<K,V>"), "\"Map<K,V>\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+    }
+}
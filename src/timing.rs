@@ -0,0 +1,115 @@
+use std::cell::Cell;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Phase {
+    Tokenize,
+    Parse,
+    Write,
+}
+
+const PHASES: [Phase; 3] = [Phase::Tokenize, Phase::Parse, Phase::Write];
+
+impl Phase {
+    fn get_name(&self) -> &str {
+        match self {
+            Phase::Tokenize => "tokenize",
+            Phase::Parse => "parse",
+            Phase::Write => "write",
+        }
+    }
+}
+
+/// Elapsed time per compiler phase, summed over every compiled file.
+#[derive(Default)]
+pub struct PhaseTimes {
+    tokenize: Cell<Duration>,
+    parse: Cell<Duration>,
+    write: Cell<Duration>,
+}
+
+impl PhaseTimes {
+    pub fn new() -> PhaseTimes {
+        PhaseTimes::default()
+    }
+
+    fn get_cell(&self, phase: Phase) -> &Cell<Duration> {
+        match phase {
+            Phase::Tokenize => &self.tokenize,
+            Phase::Parse => &self.parse,
+            Phase::Write => &self.write,
+        }
+    }
+
+    pub fn record(&self, phase: Phase, elapsed: Duration) {
+        let cell = self.get_cell(phase);
+        cell.set(cell.get() + elapsed);
+    }
+
+    /// Runs `action`, adding the time it took to `phase`.
+    pub fn time<T>(&self, phase: Phase, action: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = action();
+        self.record(phase, start.elapsed());
+
+        result
+    }
+
+    pub fn get(&self, phase: Phase) -> Duration {
+        self.get_cell(phase).get()
+    }
+
+    pub fn total(&self) -> Duration {
+        PHASES.iter().map(|phase| self.get(*phase)).sum()
+    }
+
+    pub fn report(&self) -> Vec<String> {
+        let mut result: Vec<String> = PHASES
+            .iter()
+            .map(|phase| format!("{:<9} {:?}", phase.get_name(), self.get(*phase)))
+            .collect();
+
+        result.push(format!("{:<9} {:?}", "total", self.total()));
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn record_sums_per_phase() {
+        let times = PhaseTimes::new();
+
+        times.record(Phase::Tokenize, Duration::from_millis(2));
+        times.record(Phase::Parse, Duration::from_millis(5));
+        times.record(Phase::Tokenize, Duration::from_millis(3));
+        times.record(Phase::Write, Duration::from_millis(1));
+
+        assert_eq!(times.get(Phase::Tokenize), Duration::from_millis(5));
+        assert_eq!(times.get(Phase::Parse), Duration::from_millis(5));
+        assert_eq!(times.get(Phase::Write), Duration::from_millis(1));
+        assert_eq!(times.total(), Duration::from_millis(11));
+
+        let report = times.report();
+        assert_eq!(report.len(), 4);
+        assert_eq!(report.first().unwrap(), "tokenize  5ms");
+        assert_eq!(report.last().unwrap(), "total     11ms");
+    }
+
+    #[test]
+    fn time_records_the_action() {
+        let times = PhaseTimes::new();
+
+        let value = times.time(Phase::Parse, || {
+            std::thread::sleep(Duration::from_millis(2));
+            42
+        });
+
+        assert_eq!(value, 42);
+        assert!(times.get(Phase::Parse) >= Duration::from_millis(2));
+        assert_eq!(times.get(Phase::Write), Duration::from_millis(0));
+    }
+}
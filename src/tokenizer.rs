@@ -1,26 +1,91 @@
 use std::cell::Cell;
+use std::fmt;
+
+use crate::{error::CompileError, extension::StatementRegistry};
 
 const OP_SYMBOLS: [&str; 9] = ["+", "-", "*", "/", "&", "|", ">", "<", "="];
 pub const UNARY_OP_SYMBOLS: [&str; 2] = ["-", "~"];
+pub const KEYWORD_CONSTANTS: [&str; 4] = ["true", "false", "null", "this"];
+pub const DEFAULT_TAB_WIDTH: usize = 1;
+pub const DEFAULT_MAX_LINE_LENGTH: usize = 10_000;
+pub const DEFAULT_MAX_TOKEN_LENGTH: usize = 1_000;
+
+/// Sizes past which the input is rejected as generated or garbage, before it
+/// takes a lot of memory.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Limits {
+    pub max_line_length: usize,
+    pub max_token_length: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_line_length: DEFAULT_MAX_LINE_LENGTH,
+            max_token_length: DEFAULT_MAX_TOKEN_LENGTH,
+        }
+    }
+}
 
 pub struct Tokenizer {
     tokens: Vec<TokenItem>,
     cursor: Cell<usize>,
+    statement_registry: StatementRegistry,
 }
 
 impl Tokenizer {
+    /// Tokenizes code known to be valid, such as test snippets, panicking
+    /// with the error otherwise. Use `with_limits` for user input.
     pub fn new(code: &str) -> Tokenizer {
-        let tokens = process_code(&code);
+        Tokenizer::from_tokens(process_code(code).unwrap_or_else(|error| panic!("{}", error)))
+    }
+
+    /// Tokenizes `code` moving columns to the next multiple of `tab_width`
+    /// on each `\t`, so positions line up with the user's editor.
+    pub fn with_tab_width(code: &str, tab_width: usize) -> Result<Tokenizer, CompileError> {
+        Tokenizer::with_limits(code, tab_width, Limits::default())
+    }
+
+    /// Tokenizes `code` like `with_tab_width`, failing on lines or tokens
+    /// longer than `limits` allow.
+    pub fn with_limits(
+        code: &str,
+        tab_width: usize,
+        limits: Limits,
+    ) -> Result<Tokenizer, CompileError> {
+        Ok(Tokenizer::from_tokens(process_code_with(
+            code, tab_width, limits,
+        )?))
+    }
+
+    pub fn from_tokens(tokens: Vec<TokenItem>) -> Tokenizer {
         Tokenizer {
             tokens,
             cursor: Cell::new(0),
+            statement_registry: StatementRegistry::new(),
         }
     }
 
+    pub fn get_statement_registry(&self) -> &StatementRegistry {
+        &self.statement_registry
+    }
+
+    pub fn set_statement_registry(&mut self, registry: StatementRegistry) {
+        self.statement_registry = registry;
+    }
+
+    pub fn get_tokens(&self) -> &Vec<TokenItem> {
+        &self.tokens
+    }
+
     pub fn reset(&self) {
         self.cursor.set(0);
     }
 
+    pub fn get_cursor(&self) -> usize {
+        self.cursor.get()
+    }
+
     pub fn has_next(&self) -> bool {
         self.tokens.len() > self.cursor.get()
     }
@@ -43,89 +108,147 @@ impl Tokenizer {
         None
     }
 
-    pub fn consume(&self, value: &str) -> TokenItem {
-        let token = self.get_next().unwrap();
+    /// Position of the last token, used to report an unexpected end of file.
+    pub fn get_last_position(&self) -> Position {
+        self.tokens
+            .last()
+            .map(|token| token.get_position())
+            .unwrap_or_default()
+    }
+
+    /// Looks `offset` tokens past the next one without moving the cursor.
+    pub fn peek_at(&self, offset: usize) -> Option<&TokenItem> {
+        self.tokens.get(self.cursor.get() + offset)
+    }
+
+    pub fn consume(&self, value: &str) -> Result<TokenItem, CompileError> {
+        let expected = format!("'{}'", value);
+        let token = self.next_or_end(&expected)?;
 
         if token.get_value() != value {
-            panic!(
-                "Invalid token found. Expected {} and received {}",
-                value,
-                token.get_value()
-            )
+            return Err(unexpected_token(&expected, token));
         }
 
-        token.clone()
+        Ok(token.clone())
     }
 
-    pub fn retrieve_identifier(&self) -> TokenItem {
+    pub fn retrieve_identifier(&self) -> Result<TokenItem, CompileError> {
         self.retrieve(TokenType::Identifier)
     }
 
-    pub fn retrieve_symbol(&self) -> TokenItem {
+    pub fn retrieve_symbol(&self) -> Result<TokenItem, CompileError> {
         self.retrieve(TokenType::Symbol)
     }
 
-    pub fn retrieve_keyword(&self) -> TokenItem {
+    pub fn retrieve_keyword(&self) -> Result<TokenItem, CompileError> {
         self.retrieve(TokenType::Keyword)
     }
 
-    fn retrieve(&self, expected_type: TokenType) -> TokenItem {
-        let token = self.get_next().unwrap();
+    fn retrieve(&self, expected_type: TokenType) -> Result<TokenItem, CompileError> {
+        self.retrieve_any(Vec::from([expected_type]))
+    }
 
-        if token.get_type() != expected_type {
-            panic!(
-                "Invalid token type found. Expected {:?} and received {:?}",
-                expected_type,
-                token.get_type()
-            )
+    pub fn retrieve_type(&self) -> Result<TokenItem, CompileError> {
+        let type_keywords: [&str; 3] = ["int", "char", "boolean"];
+        let expected = "a type (int, char, boolean or a class name)";
+        let token = self.next_or_end(expected)?;
+
+        let is_type = match token.get_type() {
+            TokenType::Identifier => true,
+            TokenType::Keyword => type_keywords.contains(&token.get_value().as_str()),
+            _ => false,
+        };
+
+        if !is_type {
+            return Err(unexpected_token(expected, token));
         }
 
-        token.clone()
+        Ok(token.clone())
     }
 
-    pub fn retrieve_type(&self) -> TokenItem {
-        let type_keywords: [&str; 3] = ["int", "char", "boolean"];
-        let token = self.retrieve_any(Vec::from([TokenType::Identifier, TokenType::Keyword]));
-
-        if token.get_type() == TokenType::Keyword {
-            if !type_keywords.contains(&token.get_value().as_str()) {
-                panic!(format!(
-                    "Invalid keywork. Expected {:?}, but found {}",
-                    type_keywords,
-                    token.get_value()
-                ));
-            }
+    pub fn retrieve_op(&self) -> Result<TokenItem, CompileError> {
+        let expected = "an operator";
+        let token = self.next_or_end(expected)?;
+
+        if !token.is_op() {
+            return Err(unexpected_token(expected, token));
         }
 
-        token.clone()
+        Ok(token.clone())
     }
 
-    pub fn retrieve_op(&self) -> TokenItem {
-        let token = self.retrieve_symbol();
-        let token_value = token.get_value();
+    pub fn retrieve_any(&self, expected_type: Vec<TokenType>) -> Result<TokenItem, CompileError> {
+        let expected = expected_type
+            .iter()
+            .map(|token_type| token_type.describe())
+            .collect::<Vec<&str>>()
+            .join(" or ");
+        let token = self.next_or_end(&expected)?;
 
-        if !OP_SYMBOLS.contains(&token_value.as_str()) {
-            panic!(format!(
-                "Invalid op. Expected {:?}, but found {}",
-                OP_SYMBOLS, token_value
-            ));
+        if !expected_type.contains(&token.get_type()) {
+            return Err(unexpected_token(&expected, token));
         }
 
-        token
+        Ok(token.clone())
     }
 
-    pub fn retrieve_any(&self, expected_type: Vec<TokenType>) -> TokenItem {
-        let token = self.get_next().unwrap();
+    /// Moves past the next token, failing with what was `expected` when the
+    /// code has already ended.
+    fn next_or_end(&self, expected: &str) -> Result<&TokenItem, CompileError> {
+        let position = self.get_last_position();
 
-        if !expected_type.contains(&token.get_type()) {
-            panic!(
-                "Invalid token type found. Expected {:?} and received {:?}",
-                expected_type,
-                token.get_type()
+        self.get_next().ok_or_else(|| {
+            CompileError::syntax(
+                format!("unexpected end of file, expected {}", expected).as_str(),
+                position,
             )
+        })
+    }
+}
+
+fn unexpected_token(expected: &str, token: &TokenItem) -> CompileError {
+    CompileError::syntax(
+        format!("expected {}, found '{}'", expected, token.get_value()).as_str(),
+        token.get_position(),
+    )
+}
+
+#[derive(PartialEq, Debug, Clone, Copy, Default)]
+pub struct Position {
+    line: usize,
+    column: usize,
+}
+
+impl Position {
+    pub fn new(line: usize, column: usize) -> Position {
+        Position { line, column }
+    }
+
+    pub fn get_line(&self) -> usize {
+        self.line
+    }
+
+    pub fn get_column(&self) -> usize {
+        self.column
+    }
+
+    fn advance(&self, c: char, tab_width: usize) -> Position {
+        if c == '\n' {
+            return Position::new(self.line + 1, 1);
         }
 
-        token.clone()
+        if c == '\t' && tab_width > 1 {
+            let next_stop = ((self.column - 1) / tab_width + 1) * tab_width;
+            return Position::new(self.line, next_stop + 1);
+        }
+
+        Position::new(self.line, self.column + 1)
+    }
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
     }
 }
 
@@ -133,6 +256,7 @@ impl Tokenizer {
 pub struct TokenItem {
     token_type: TokenType,
     value: String,
+    position: Position,
 }
 
 impl TokenItem {
@@ -140,9 +264,19 @@ impl TokenItem {
         TokenItem {
             value: String::from(value),
             token_type,
+            position: Position::default(),
         }
     }
 
+    pub fn with_position(mut self, position: Position) -> TokenItem {
+        self.position = position;
+        self
+    }
+
+    pub fn get_position(&self) -> Position {
+        self.position
+    }
+
     pub fn get_type(&self) -> TokenType {
         self.token_type
     }
@@ -166,28 +300,78 @@ pub enum TokenType {
     None,
 }
 
-fn process_code(code: &str) -> Vec<TokenItem> {
+impl TokenType {
+    /// How the type reads inside an error message, like "an identifier".
+    pub fn describe(&self) -> &'static str {
+        match self {
+            TokenType::String => "a string",
+            TokenType::Integer => "an integer",
+            TokenType::Symbol => "a symbol",
+            TokenType::Identifier => "an identifier",
+            TokenType::Keyword => "a keyword",
+            TokenType::None => "a token",
+        }
+    }
+}
+
+fn process_code(code: &str) -> Result<Vec<TokenItem>, CompileError> {
+    process_code_with(code, DEFAULT_TAB_WIDTH, Limits::default())
+}
+
+fn process_code_with(
+    code: &str,
+    tab_width: usize,
+    limits: Limits,
+) -> Result<Vec<TokenItem>, CompileError> {
+    for (index, line) in code.lines().enumerate() {
+        let length = line.chars().count();
+
+        if length > limits.max_line_length {
+            return Err(CompileError::syntax(
+                format!(
+                    "line is {} characters long, more than the limit of {}",
+                    length, limits.max_line_length
+                )
+                .as_str(),
+                Position::new(index + 1, 1),
+            ));
+        }
+    }
+
     let mut start_token_position: usize = 0;
+    let mut start_position = Position::new(1, 1);
+    let mut position = Position::new(1, 1);
     let mut current_type = TokenType::None;
     let mut result: Vec<TokenItem> = Vec::new();
 
-    for (i, c) in code.chars().enumerate() {
+    for (i, c) in code.char_indices() {
+        let current_position = position;
+        position = position.advance(c, tab_width);
+
         if c == '"' {
             match current_type {
                 TokenType::None => {
                     start_token_position = i;
+                    start_position = current_position;
                     current_type = TokenType::String;
                 }
                 TokenType::String => {
-                    result.push(build_token(&code[start_token_position..(i + 1)]));
+                    result.push(build_limited_token(
+                        &code[start_token_position..(i + 1)],
+                        start_position,
+                        limits,
+                    )?);
                     start_token_position = i + 1;
                     current_type = TokenType::None;
                     continue;
                 }
-                _ => panic!(format!(
-                    "Invalid presence of \" inside a {:?}",
-                    current_type
-                )),
+                _ => {
+                    return Err(CompileError::syntax(
+                        format!("unexpected '\"' after '{}'", &code[start_token_position..i])
+                            .as_str(),
+                        current_position,
+                    ))
+                }
             }
         }
 
@@ -195,12 +379,16 @@ fn process_code(code: &str) -> Vec<TokenItem> {
             continue;
         }
 
-        if c == ' ' {
+        if c.is_whitespace() {
             if i - start_token_position > 0 {
-                result.push(build_token(&code[start_token_position..i]));
+                result.push(build_limited_token(
+                    &code[start_token_position..i],
+                    start_position,
+                    limits,
+                )?);
             }
 
-            start_token_position = i + 1;
+            start_token_position = i + c.len_utf8();
             current_type = TokenType::None;
 
             continue;
@@ -208,10 +396,14 @@ fn process_code(code: &str) -> Vec<TokenItem> {
 
         if is_symbol(c) {
             if i - start_token_position > 0 {
-                result.push(build_token(&code[start_token_position..i]));
+                result.push(build_limited_token(
+                    &code[start_token_position..i],
+                    start_position,
+                    limits,
+                )?);
             }
 
-            result.push(build_token(&c.to_string()));
+            result.push(build_token(&c.to_string(), current_position)?);
             start_token_position = i + 1;
             current_type = TokenType::None;
 
@@ -220,44 +412,92 @@ fn process_code(code: &str) -> Vec<TokenItem> {
 
         if c.is_numeric() && current_type == TokenType::None {
             start_token_position = i;
+            start_position = current_position;
             current_type = TokenType::Integer;
         }
 
+        // a digit glued to letters (like `2foo`) is kept as a single token,
+        // so build_token can reject it as an invalid identifier
         if current_type == TokenType::Integer && !c.is_numeric() {
-            panic!("Non numeric char mixed inside a Integer token");
+            current_type = TokenType::Identifier;
         }
 
         if current_type == TokenType::None {
             start_token_position = i;
+            start_position = current_position;
             current_type = TokenType::Identifier;
         }
     }
 
     if code.len() - start_token_position > 0 {
-        result.push(build_token(&code[start_token_position..]));
+        result.push(build_limited_token(
+            &code[start_token_position..],
+            start_position,
+            limits,
+        )?);
     }
 
-    result
+    Ok(result)
 }
 
-fn build_token(value: &str) -> TokenItem {
-    if value.len() == 1 && is_symbol(value.chars().nth(0).unwrap()) {
-        return TokenItem::new(value, TokenType::Symbol);
+fn build_limited_token(
+    value: &str,
+    position: Position,
+    limits: Limits,
+) -> Result<TokenItem, CompileError> {
+    let length = value.chars().count();
+
+    if length > limits.max_token_length {
+        return Err(CompileError::syntax(
+            format!(
+                "token is {} characters long, more than the limit of {}",
+                length, limits.max_token_length
+            )
+            .as_str(),
+            position,
+        ));
+    }
+
+    build_token(value, position)
+}
+
+fn build_token(value: &str, position: Position) -> Result<TokenItem, CompileError> {
+    let token_type =
+        token_type_of(value).map_err(|message| CompileError::syntax(message.as_str(), position))?;
+
+    let value = match token_type {
+        TokenType::String => value.replace("\"", ""),
+        _ => String::from(value),
+    };
+
+    Ok(TokenItem::new(&value, token_type).with_position(position))
+}
+
+fn token_type_of(value: &str) -> Result<TokenType, String> {
+    if value.len() == 1 && is_symbol(value.chars().next().unwrap()) {
+        return Ok(TokenType::Symbol);
     }
 
     if is_keyword(value) {
-        return TokenItem::new(value, TokenType::Keyword);
+        return Ok(TokenType::Keyword);
+    }
+
+    if is_string(value)? {
+        return Ok(TokenType::String);
     }
 
-    if is_string(value) {
-        return TokenItem::new(&value.replace("\"", ""), TokenType::String);
+    if is_integer(value)? {
+        return Ok(TokenType::Integer);
     }
 
-    if is_integer(value) {
-        return TokenItem::new(&value.replace("\"", ""), TokenType::Integer);
+    if !is_identifier(value) {
+        return Err(format!(
+            "invalid identifier '{}': identifiers must match [A-Za-z_][A-Za-z0-9_]*",
+            value
+        ));
     }
 
-    TokenItem::new(value, TokenType::Identifier)
+    Ok(TokenType::Identifier)
 }
 
 fn is_symbol(c: char) -> bool {
@@ -297,36 +537,45 @@ fn is_keyword(value: &str) -> bool {
     keywords.contains(&value)
 }
 
-fn is_string(value: &str) -> bool {
+fn is_string(value: &str) -> Result<bool, String> {
     if value.starts_with('"') {
-        if value.ends_with('"') {
-            return true;
+        if value.len() > 1 && value.ends_with('"') {
+            return Ok(true);
         }
-        panic!(format!(
-            "Incomplete string: '{}' starts with \" but not ends with \"",
-            value
-        ));
+        return Err(format!("unterminated string: {}", value));
     }
-    false
+    Ok(false)
 }
 
-fn is_integer(value: &str) -> bool {
+fn is_integer(value: &str) -> Result<bool, String> {
     for c in value.chars() {
         if !c.is_numeric() {
-            return false;
+            return Ok(false);
         }
     }
 
-    // panics on long numeric sequences that are out of i16 range
+    // long numeric sequences are out of i16 range
     let parsed = value.parse::<i16>();
     if parsed.is_err() {
-        panic!(format!(
-            "Invalid numeric value: {}. Failed to parse to i16",
-            value
+        return Err(format!(
+            "integer {} is out of range, the maximum is {}",
+            value,
+            i16::MAX
         ));
     }
 
-    true
+    Ok(true)
+}
+
+fn is_identifier(value: &str) -> bool {
+    let mut chars = value.chars();
+
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+            chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        }
+        _ => false,
+    }
 }
 
 #[cfg(test)]
@@ -335,7 +584,7 @@ mod tests {
 
     #[test]
     fn test_build_token_symbol() {
-        let token = build_token("(");
+        let token = build_token("(", Position::default()).unwrap();
 
         assert_eq!(token.get_type(), TokenType::Symbol);
         assert_eq!(token.get_value(), "(");
@@ -343,22 +592,22 @@ mod tests {
 
     #[test]
     fn test_process_code_symbol() {
-        let result = process_code("(");
+        let result = process_code("(").unwrap();
 
         assert_eq!(result.len(), 1);
 
-        let token = result.get(0).unwrap();
+        let token = result.first().unwrap();
         assert_eq!(token.get_type(), TokenType::Symbol);
         assert_eq!(token.get_value(), "(");
     }
 
     #[test]
     fn test_process_code_identifier_and_symbol() {
-        let result = process_code("test(");
+        let result = process_code("test(").unwrap();
 
         assert_eq!(result.len(), 2);
 
-        let token = result.get(0).unwrap();
+        let token = result.first().unwrap();
         assert_eq!(token.get_type(), TokenType::Identifier);
         assert_eq!(token.get_value(), "test");
 
@@ -369,65 +618,192 @@ mod tests {
 
     #[test]
     fn test_process_code_keyword() {
-        let result = process_code("class");
+        let result = process_code("class").unwrap();
 
         assert_eq!(result.len(), 1);
 
-        let token = result.get(0).unwrap();
+        let token = result.first().unwrap();
         assert_eq!(token.get_type(), TokenType::Keyword);
         assert_eq!(token.get_value(), "class");
     }
 
+    #[test]
+    fn test_process_code_positions() {
+        let result = process_code("class Main {\n    field int x;\n}").unwrap();
+
+        let token = result.first().unwrap();
+        assert_eq!(token.get_value(), "class");
+        assert_eq!(token.get_position(), Position::new(1, 1));
+
+        let token = result.get(2).unwrap();
+        assert_eq!(token.get_value(), "{");
+        assert_eq!(token.get_position(), Position::new(1, 12));
+
+        let token = result.get(4).unwrap();
+        assert_eq!(token.get_value(), "int");
+        assert_eq!(token.get_position(), Position::new(2, 11));
+
+        let token = result.get(6).unwrap();
+        assert_eq!(token.get_value(), ";");
+        assert_eq!(token.get_position(), Position::new(2, 16));
+
+        let token = result.get(7).unwrap();
+        assert_eq!(token.get_value(), "}");
+        assert_eq!(token.get_position(), Position::new(3, 1));
+    }
+
+    #[test]
+    fn test_process_code_positions_with_tab_width() {
+        let code = "class Main {\n\tfield int x;\n  \tfield int y;\n}";
+
+        let result = process_code_with(code, 4, Limits::default()).unwrap();
+        assert_eq!(result.get(3).unwrap().get_position(), Position::new(2, 5));
+        assert_eq!(result.get(7).unwrap().get_position(), Position::new(3, 5));
+
+        let result = process_code(code).unwrap();
+        assert_eq!(result.get(3).unwrap().get_position(), Position::new(2, 2));
+        assert_eq!(result.get(7).unwrap().get_position(), Position::new(3, 4));
+    }
+
     #[test]
     fn test_retrieve_type() {
         let tokenizer = Tokenizer::new("int x");
 
-        let token = tokenizer.retrieve_type();
+        let token = tokenizer.retrieve_type().unwrap();
 
         assert_eq!(token.get_value(), "int");
     }
 
     #[test]
-    #[should_panic(expected = "Invalid presence of \" inside a Identifier")]
     fn test_process_code_invalid_quote() {
-        let _ = process_code("test\"");
+        let result = process_code("test\"");
+
+        assert_eq!(
+            result.err().unwrap(),
+            CompileError::syntax("unexpected '\"' after 'test'", Position::new(1, 5))
+        );
     }
 
     #[test]
-    #[should_panic(expected = "Incomplete string: '\"test)' starts with \" but not ends with \"")]
     fn test_process_code_with_invalid_string() {
-        let _ = process_code("print(\"test)");
+        let result = process_code("print(\"test)");
+
+        assert_eq!(
+            result.err().unwrap(),
+            CompileError::syntax("unterminated string: \"test)", Position::new(1, 7))
+        );
     }
 
     #[test]
-    #[should_panic(expected = "Non numeric char mixed inside a Integer token")]
     fn test_process_code_number_with_invalid_char() {
-        let _ = process_code("x = 23a");
+        let result = process_code("x = 23a");
+
+        assert_eq!(
+            result.err().unwrap(),
+            CompileError::syntax(
+                "invalid identifier '23a': identifiers must match [A-Za-z_][A-Za-z0-9_]*",
+                Position::new(1, 5)
+            )
+        );
+    }
+
+    #[test]
+    fn test_build_token_identifier_with_underscore_and_digit() {
+        let token = build_token("_foo", Position::default()).unwrap();
+        assert_eq!(token.get_type(), TokenType::Identifier);
+        assert_eq!(token.get_value(), "_foo");
+
+        let token = build_token("foo2", Position::default()).unwrap();
+        assert_eq!(token.get_type(), TokenType::Identifier);
+        assert_eq!(token.get_value(), "foo2");
+    }
+
+    #[test]
+    fn test_process_code_identifier_starting_with_digit() {
+        let result = process_code("let 2foo = 1;");
+
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "1:5: invalid identifier '2foo': identifiers must match [A-Za-z_][A-Za-z0-9_]*"
+        );
+    }
+
+    #[test]
+    fn test_process_code_identifier_too_long() {
+        let result = process_code(&format!("let {} = 1;", "a".repeat(5000)));
+
+        assert_eq!(
+            result.err().unwrap(),
+            CompileError::syntax(
+                "token is 5000 characters long, more than the limit of 1000",
+                Position::new(1, 5)
+            )
+        );
+    }
+
+    #[test]
+    fn test_process_code_line_too_long() {
+        let limits = Limits {
+            max_line_length: 30,
+            ..Limits::default()
+        };
+
+        let result = process_code_with("let x = 1;\nlet total = x + x + x + x + 10;", 1, limits);
+
+        assert_eq!(
+            result.err().unwrap(),
+            CompileError::syntax(
+                "line is 31 characters long, more than the limit of 30",
+                Position::new(2, 1)
+            )
+        );
     }
 
     #[test]
-    #[should_panic(expected = "Invalid numeric value: 32768. Failed to parse to i16")]
     fn test_process_code_number_too_big() {
-        let _ = process_code("x = 32768");
+        let result = process_code("x = 32768");
+
+        assert_eq!(
+            result.err().unwrap(),
+            CompileError::syntax(
+                "integer 32768 is out of range, the maximum is 32767",
+                Position::new(1, 5)
+            )
+        );
     }
 
     #[test]
-    #[should_panic(
-        expected = "Invalid keywork. Expected [\"int\", \"char\", \"boolean\"], but found void"
-    )]
     fn test_retrieve_invalid_type() {
         let tokenizer = Tokenizer::new("void x");
 
-        let _ = tokenizer.retrieve_type();
+        let result = tokenizer.retrieve_type();
+
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "1:1: expected a type (int, char, boolean or a class name), found 'void'"
+        );
+    }
+
+    #[test]
+    fn test_consume_at_end_of_file() {
+        let tokenizer = Tokenizer::new("return");
+        tokenizer.consume("return").unwrap();
+
+        let result = tokenizer.consume(";");
+
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "1:1: unexpected end of file, expected ';'"
+        );
     }
 
     #[test]
     fn test_process_code_call_method_with_string() {
-        let result = process_code("print(\"big string\")");
+        let result = process_code("print(\"big string\")").unwrap();
 
         assert_eq!(result.len(), 4);
 
-        let token = result.get(0).unwrap();
+        let token = result.first().unwrap();
         assert_eq!(token.get_type(), TokenType::Identifier);
         assert_eq!(token.get_value(), "print");
 
@@ -446,11 +822,11 @@ mod tests {
 
     #[test]
     fn test_process_code_sum_two_numbers() {
-        let result = process_code("5 +   7");
+        let result = process_code("5 +   7").unwrap();
 
         assert_eq!(result.len(), 3);
 
-        let token = result.get(0).unwrap();
+        let token = result.first().unwrap();
         assert_eq!(token.get_type(), TokenType::Integer);
         assert_eq!(token.get_value(), "5");
 
@@ -464,11 +840,11 @@ mod tests {
     }
     #[test]
     fn test_process_code_long_command() {
-        let result = process_code("do    Output.printInt(   sum / length  );");
+        let result = process_code("do    Output.printInt(   sum / length  );").unwrap();
 
         assert_eq!(result.len(), 10);
 
-        let token = result.get(0).unwrap();
+        let token = result.first().unwrap();
         assert_eq!(token.get_type(), TokenType::Keyword);
         assert_eq!(token.get_value(), "do");
 
@@ -0,0 +1,474 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+
+/// Every command of the VM language, plus the `shl` and `shr` extensions of
+/// targets that support shifts.
+const VM_OPCODES: [&str; 19] = [
+    "push", "pop", "add", "sub", "neg", "eq", "gt", "lt", "and", "or", "not", "shl", "shr",
+    "label", "goto", "if-goto", "function", "call", "return",
+];
+
+/// The code of a single compiled subroutine, starting at its `function` line.
+pub struct VmFunction {
+    name: String,
+    code: Vec<String>,
+}
+
+impl VmFunction {
+    pub fn get_name(&self) -> &String {
+        &self.name
+    }
+
+    pub fn get_code(&self) -> &Vec<String> {
+        &self.code
+    }
+}
+
+/// The VM code of a class, kept as lines but queryable by function.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VmProgram {
+    lines: Vec<String>,
+}
+
+impl VmProgram {
+    pub fn new(lines: Vec<String>) -> VmProgram {
+        VmProgram { lines }
+    }
+
+    pub fn get_lines(&self) -> &Vec<String> {
+        &self.lines
+    }
+
+    pub fn get_functions(&self) -> Vec<VmFunction> {
+        split_functions(&self.lines)
+    }
+
+    /// The code of the function named `name` (like `Main.main`), from its
+    /// `function` line to the line before the next one.
+    pub fn get_function(&self, name: &str) -> Option<VmFunction> {
+        self.get_functions()
+            .into_iter()
+            .find(|function| function.get_name() == name)
+    }
+
+    pub fn into_lines(self) -> Vec<String> {
+        self.lines
+    }
+}
+
+impl fmt::Display for VmProgram {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.lines.join("\r\n"))
+    }
+}
+
+/// Splits the code of a class on its `function` commands. Anything written
+/// before the first function is dropped, since it belongs to no subroutine.
+pub fn split_functions(code: &[String]) -> Vec<VmFunction> {
+    let mut result: Vec<VmFunction> = Vec::new();
+
+    for line in code {
+        if let Some(name) = function_name(line) {
+            result.push(VmFunction {
+                name,
+                code: Vec::new(),
+            });
+        }
+
+        if let Some(function) = result.last_mut() {
+            function.code.push(line.clone());
+        }
+    }
+
+    result
+}
+
+fn function_name(line: &str) -> Option<String> {
+    let mut parts = line.split_whitespace();
+
+    match (parts.next(), parts.next()) {
+        (Some("function"), Some(name)) => Some(String::from(name)),
+        _ => None,
+    }
+}
+
+/// Trims each command, collapses the spaces inside it and drops `//` comments
+/// and blank lines.
+pub fn normalize_whitespace(vm: &[String]) -> Vec<String> {
+    vm.iter()
+        .map(|line| line.split("//").next().unwrap_or_default())
+        .map(|line| line.split_whitespace().collect::<Vec<&str>>().join(" "))
+        .filter(|line| !line.is_empty())
+        .collect()
+}
+
+/// A form of `vm` for comparing the output of different compilers: whitespace
+/// is normalized and labels are renamed `L0`, `L1`, ... in order of first use
+/// inside each function. Labels are function scoped in the VM, and each name
+/// keeps mapping to a single new one, so control flow is unchanged.
+pub fn canonicalize(vm: &[String]) -> Vec<String> {
+    let mut labels: HashMap<String, String> = HashMap::new();
+    let mut result = Vec::new();
+
+    for line in normalize_whitespace(vm) {
+        if function_name(&line).is_some() {
+            labels.clear();
+        }
+
+        let mut parts = line.splitn(2, ' ');
+
+        match (parts.next(), parts.next()) {
+            (Some(command @ ("label" | "goto" | "if-goto")), Some(label)) => {
+                let next = format!("L{}", labels.len());
+                let label = labels.entry(String::from(label)).or_insert(next);
+
+                result.push(format!("{} {}", command, label));
+            }
+            _ => result.push(line),
+        }
+    }
+
+    result
+}
+
+/// Writes `vm` on a single line, separating commands with `;`. A `;` or `\`
+/// inside a command (like in a `;` comment) is escaped with `\`, so
+/// `parse_compact` gives the lines back unchanged.
+pub fn compact(vm: &[String]) -> String {
+    vm.iter()
+        .map(|line| line.replace('\\', "\\\\").replace(';', "\\;"))
+        .collect::<Vec<String>>()
+        .join(";")
+}
+
+pub fn parse_compact(line: &str) -> Vec<String> {
+    let mut result = Vec::new();
+    let mut current = String::new();
+    let mut chars = line.chars();
+
+    if line.is_empty() {
+        return result;
+    }
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => current.extend(chars.next()),
+            ';' => result.push(std::mem::take(&mut current)),
+            c => current.push(c),
+        }
+    }
+
+    result.push(current);
+
+    result
+}
+
+/// Counts how many times each VM command (`push`, `call`, ...) appears in
+/// `code`, most used first. Only VM commands are counted, so comments are
+/// skipped whatever their prefix.
+pub fn opcode_stats(code: &[String]) -> Vec<(String, usize)> {
+    let mut counts: HashMap<String, usize> = HashMap::new();
+
+    for line in normalize_whitespace(code) {
+        if let Some(opcode) = line.split(' ').next() {
+            if VM_OPCODES.contains(&opcode) {
+                *counts.entry(String::from(opcode)).or_insert(0) += 1;
+            }
+        }
+    }
+
+    let mut result: Vec<(String, usize)> = counts.into_iter().collect();
+    result.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+    result
+}
+
+pub fn opcode_report(stats: &[(String, usize)]) -> Vec<String> {
+    let mut result: Vec<String> = stats
+        .iter()
+        .map(|(opcode, count)| format!("{:<9} {}", opcode, count))
+        .collect();
+
+    result.push(format!(
+        "{:<9} {}",
+        "total",
+        stats.iter().map(|(_, count)| count).sum::<usize>()
+    ));
+
+    result
+}
+
+/// Lists every `label` of `code` as `<Class.sub> <label>`, one per line, for
+/// coverage tools that need to know which subroutine owns each label.
+pub fn labels_manifest(code: &[String]) -> Vec<String> {
+    let mut result = Vec::new();
+
+    for function in split_functions(code) {
+        for line in function.get_code() {
+            if let Some(label) = line.strip_prefix("label ") {
+                result.push(format!("{} {}", function.get_name(), label));
+            }
+        }
+    }
+
+    result
+}
+
+/// Joins the code of every class of a program behind a single call to
+/// `entry`, for runtimes that load one file and start at its first command.
+pub fn bootstrap_program(entry: &str, classes: &[Vec<String>]) -> Vec<String> {
+    let mut result = vec![String::from("// bootstrap"), format!("call {} 0", entry)];
+
+    for code in classes {
+        result.extend(code.iter().cloned());
+    }
+
+    result
+}
+
+/// The path and contents of one `<folder of filename>/<Class.sub>.vm` file per
+/// function of `code`.
+pub fn split_files(filename: &str, code: &[String]) -> Vec<(String, String)> {
+    let folder = Path::new(filename)
+        .parent()
+        .unwrap_or_else(|| Path::new(""));
+
+    split_functions(code)
+        .iter()
+        .map(|function| {
+            let path = folder.join(format!("{}.vm", function.get_name()));
+
+            (
+                path.to_string_lossy().into_owned(),
+                function.get_code().join("\r\n"),
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parser::ClassNode, tokenizer::Tokenizer, writer::VmWriter};
+
+    fn compile(source: &str) -> Vec<String> {
+        let tokenizer = Tokenizer::new(source);
+        let tree = ClassNode::build(&tokenizer).unwrap();
+
+        VmWriter::new().build(&tree)
+    }
+
+    #[test]
+    fn query_function_from_program() {
+        let source = "class Main { function void main() { do Main.run(); return; } function int run() { return 1; } }";
+        let tokenizer = Tokenizer::new(source);
+        let tree = ClassNode::build(&tokenizer).unwrap();
+
+        let program = VmWriter::new().build_program(&tree);
+
+        assert_eq!(program.get_functions().len(), 2);
+        assert_eq!(
+            program.get_function("Main.run").unwrap().get_code(),
+            &vec!["function Main.run 0", "push constant 1", "return"]
+        );
+        assert!(program.get_function("Main.stop").is_none());
+        assert_eq!(program.to_string(), program.get_lines().join("\r\n"));
+    }
+
+    #[test]
+    fn bootstrap_is_emitted_once() {
+        let classes = vec![
+            compile("class Main { function void main() { do Other.run(); return; } }"),
+            compile("class Other { function void run() { return; } }"),
+        ];
+
+        let program = bootstrap_program("Main.main", &classes);
+
+        assert_eq!(program.first().unwrap(), "// bootstrap");
+        assert_eq!(program.get(1).unwrap(), "call Main.main 0");
+        assert_eq!(
+            program
+                .iter()
+                .filter(|line| line.as_str() == "call Main.main 0")
+                .count(),
+            1
+        );
+        assert_eq!(program.len(), 2 + classes[0].len() + classes[1].len());
+    }
+
+    #[test]
+    fn canonicalize_ignores_label_numbers_and_spacing() {
+        let ours: Vec<String> = vec![
+            "function Main.main 0",
+            "label WHILE_EXP3",
+            "push constant 0",
+            "if-goto WHILE_END3",
+            "goto WHILE_EXP3",
+            "label WHILE_END3",
+            "push constant 0",
+            "return",
+            "// end function Main.main",
+        ]
+        .into_iter()
+        .map(String::from)
+        .collect();
+        let theirs: Vec<String> = vec![
+            "function Main.main 0",
+            "label WHILE_EXP0",
+            "  push   constant 0",
+            "if-goto WHILE_END0 // exit",
+            "goto WHILE_EXP0",
+            "label WHILE_END0",
+            "",
+            "push constant 0",
+            "return",
+        ]
+        .into_iter()
+        .map(String::from)
+        .collect();
+
+        assert_eq!(canonicalize(&ours), canonicalize(&theirs));
+        assert_eq!(
+            canonicalize(&ours),
+            vec![
+                "function Main.main 0",
+                "label L0",
+                "push constant 0",
+                "if-goto L1",
+                "goto L0",
+                "label L1",
+                "push constant 0",
+                "return",
+            ]
+        );
+    }
+
+    #[test]
+    fn compact_round_trip() {
+        let mut code = compile(
+            "class Main { function void main() { var int i; while (i < 3) { let i = i + 1; } return; } }",
+        );
+        code.push(String::from("; end function Main.main"));
+        code.push(String::from("// path C:\\jack"));
+        code.push(String::new());
+
+        let line = compact(&code);
+
+        assert!(!line.contains('\n'));
+        assert!(line.starts_with("function Main.main 1;label WHILE_EXP0;"));
+        assert_eq!(parse_compact(&line), code);
+        assert!(parse_compact("").is_empty());
+    }
+
+    #[test]
+    fn split_two_subroutines() {
+        let code = compile(
+            "class Main { function void main() { do Main.run(); return; } function int run() { return 1; } }",
+        );
+
+        let functions = split_functions(&code);
+
+        assert_eq!(functions.len(), 2);
+        assert_eq!(functions.first().unwrap().get_name(), "Main.main");
+        assert_eq!(
+            functions.first().unwrap().get_code().first().unwrap(),
+            "function Main.main 0"
+        );
+        assert_eq!(
+            functions.first().unwrap().get_code().last().unwrap(),
+            "return"
+        );
+        assert_eq!(functions.get(1).unwrap().get_name(), "Main.run");
+        assert_eq!(
+            functions.get(1).unwrap().get_code(),
+            &vec!["function Main.run 0", "push constant 1", "return"]
+        );
+    }
+
+    #[test]
+    fn opcode_stats_for_loop() {
+        let code = compile(
+            "class Main { function void main() { var int i; while (i < 3) { let i = i + 1; } do Output.printInt(i); return; } }",
+        );
+
+        let stats = opcode_stats(&code);
+
+        assert_eq!(
+            opcode_report(&stats),
+            vec![
+                "push      6",
+                "label     2",
+                "pop       2",
+                "add       1",
+                "call      1",
+                "function  1",
+                "goto      1",
+                "if-goto   1",
+                "lt        1",
+                "not       1",
+                "return    1",
+                "total     18",
+            ]
+        );
+    }
+
+    #[test]
+    fn opcode_stats_skip_custom_comments() {
+        let code: Vec<String> = vec![
+            "function Main.main 0",
+            "push constant 0",
+            "return",
+            "; end function Main.main",
+        ]
+        .into_iter()
+        .map(String::from)
+        .collect();
+
+        assert_eq!(
+            opcode_report(&opcode_stats(&code)),
+            vec!["function  1", "push      1", "return    1", "total     3"]
+        );
+    }
+
+    #[test]
+    fn labels_manifest_per_subroutine() {
+        let code = compile(
+            "class Main { function void main() { var int i; while (i < 3) { let i = i + 1; } return; } function void check(boolean b) { if (b) { do Main.main(); } return; } }",
+        );
+
+        let manifest = labels_manifest(&code);
+
+        assert_eq!(
+            manifest,
+            vec![
+                "Main.main WHILE_EXP0",
+                "Main.main WHILE_END0",
+                "Main.check IF_TRUE1",
+                "Main.check IF_FALSE1",
+            ]
+        );
+    }
+
+    #[test]
+    fn split_files_per_subroutine() {
+        let folder = Path::new("project").join("src");
+        let filename = folder.join("Main.jack");
+
+        let code = compile(
+            "class Main { function void main() { return; } method int run() { return 1; } }",
+        );
+
+        let files = split_files(filename.to_str().unwrap(), &code);
+
+        assert_eq!(files.len(), 2);
+
+        let (path, main) = files.first().unwrap();
+        assert_eq!(path, folder.join("Main.main.vm").to_str().unwrap());
+        assert!(main.starts_with("function Main.main 0\r\n"));
+
+        let (path, run) = files.get(1).unwrap();
+        assert_eq!(path, folder.join("Main.run.vm").to_str().unwrap());
+        assert!(run.starts_with("function Main.run 0\r\n"));
+    }
+}
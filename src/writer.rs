@@ -1,13 +1,167 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
 use crate::{
+    extension::StatementRegistry,
+    lint::Warning,
+    optimizer::{fold_expression, shift_amount, Constant},
     parser::{SymbolTable, TokenTreeItem},
     tokenizer::TokenType,
+    vm::VmProgram,
 };
 
+/// The VM runtime the generated code runs on.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Target {
+    /// The standard Hack VM from the course.
+    #[default]
+    Hack,
+    /// A Hack VM extended with `shl` and `shr`, which pop a value and a
+    /// shift amount. `shr` must truncate toward zero, like `Math.divide`.
+    ShiftVm,
+}
+
+impl Target {
+    pub fn from_name(name: &str) -> Option<Target> {
+        match name {
+            "hack" => Some(Target::Hack),
+            "shift-vm" => Some(Target::ShiftVm),
+            _ => None,
+        }
+    }
+
+    pub fn supports_shift(&self) -> bool {
+        *self == Target::ShiftVm
+    }
+}
+
+/// How a subroutine hands control back to its caller.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ReturnConvention {
+    /// A plain `return`, as the Hack VM expects.
+    #[default]
+    Standard,
+    /// Marks an epilogue before each `return` for VM variants where the
+    /// caller cleans up the arguments.
+    CallerCleanup,
+}
+
+impl ReturnConvention {
+    pub fn from_name(name: &str) -> Option<ReturnConvention> {
+        match name {
+            "standard" => Some(ReturnConvention::Standard),
+            "caller-cleanup" => Some(ReturnConvention::CallerCleanup),
+            _ => None,
+        }
+    }
+}
+
+/// How the `while` and `if` labels of a class are numbered.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum LabelNumbering {
+    /// One counter shared by every `while` and `if` of the class.
+    #[default]
+    Class,
+    /// Separate `while` and `if` counters restarting at 0 in each subroutine,
+    /// like the official Nand2Tetris compiler, so its output can be diffed.
+    Official,
+}
+
+impl LabelNumbering {
+    pub fn from_name(name: &str) -> Option<LabelNumbering> {
+        match name {
+            "class" => Some(LabelNumbering::Class),
+            "official" => Some(LabelNumbering::Official),
+            _ => None,
+        }
+    }
+}
+
+pub const DEFAULT_MAX_LOCALS: usize = 256;
+pub const DEFAULT_MAX_ARGUMENTS: usize = 32;
+
+/// Decides how many words a constructor allocates, given the class name and
+/// its field count, for runtimes that store more than the declared fields.
+pub type AllocSizeHook = Rc<dyn Fn(&str, usize) -> usize>;
+
+#[derive(Clone)]
+pub struct WriterOptions {
+    /// Replaces expressions made only of literals by their value.
+    pub fold_constants: bool,
+    /// Ends constructors with `push pointer 0; return` when their last
+    /// statement is not `return this;`, as some custom runtimes expect.
+    pub auto_return_this: bool,
+    pub target: Target,
+    /// Class used to build string constants (`new` and `appendChar`).
+    pub string_class: String,
+    /// Writes a `// end function Class.sub` comment after each subroutine.
+    pub end_function_marker: bool,
+    /// Builds string literals repeated inside a subroutine once, at its start,
+    /// keeping them in hidden locals. Jack strings are mutable, so every
+    /// occurrence then shares one object: `setCharAt`, `appendChar` or
+    /// `dispose` through one of them is seen by all the others.
+    pub dedup_strings: bool,
+    pub return_convention: ReturnConvention,
+    pub label_numbering: LabelNumbering,
+    /// Warns about subroutines declaring more locals than this. The VM could
+    /// address many more, but that many usually means generated or broken code.
+    /// `None` skips the check.
+    pub max_locals: Option<usize>,
+    /// Warns about calls passing more arguments than this, which most often
+    /// comes from a missing `)` swallowing the code after it. `None` skips
+    /// the check.
+    pub max_arguments: Option<usize>,
+    /// Starts every comment the writer emits, for VM tools expecting `;`.
+    pub comment_prefix: String,
+}
+
+impl Default for WriterOptions {
+    fn default() -> Self {
+        WriterOptions {
+            fold_constants: false,
+            auto_return_this: false,
+            target: Target::default(),
+            string_class: String::from("String"),
+            end_function_marker: false,
+            dedup_strings: false,
+            return_convention: ReturnConvention::default(),
+            label_numbering: LabelNumbering::default(),
+            max_locals: Some(DEFAULT_MAX_LOCALS),
+            max_arguments: Some(DEFAULT_MAX_ARGUMENTS),
+            comment_prefix: String::from("//"),
+        }
+    }
+}
+
+/// The size of a compiled class: the words of each instance and the statics.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClassMetadata {
+    fields: usize,
+    statics: usize,
+}
+
+impl ClassMetadata {
+    pub fn get_fields(&self) -> usize {
+        self.fields
+    }
+
+    pub fn get_statics(&self) -> usize {
+        self.statics
+    }
+}
+
 pub struct VmWriter {
     class_symbol_table: SymbolTable,
     symbol_table: SymbolTable,
     class_name: String,
     current_id: usize,
+    while_id: usize,
+    if_id: usize,
+    statement_registry: StatementRegistry,
+    alloc_size_hook: Option<AllocSizeHook>,
+    options: WriterOptions,
+    string_locals: HashMap<String, usize>,
+    warnings: Vec<Warning>,
 }
 
 impl VmWriter {
@@ -17,13 +171,45 @@ impl VmWriter {
             symbol_table: SymbolTable::new(),
             class_name: String::new(),
             current_id: 0,
+            while_id: 0,
+            if_id: 0,
+            statement_registry: StatementRegistry::new(),
+            alloc_size_hook: None,
+            options: WriterOptions::default(),
+            string_locals: HashMap::new(),
+            warnings: Vec::new(),
         }
     }
 
+    pub fn with_options(options: WriterOptions) -> VmWriter {
+        let mut writer = VmWriter::new();
+        writer.options = options;
+
+        writer
+    }
+
+    pub fn set_statement_registry(&mut self, registry: StatementRegistry) {
+        self.statement_registry = registry;
+    }
+
+    /// Overrides the size constructors pass to `Memory.alloc`, which is the
+    /// field count by default (at least 1).
+    pub fn set_alloc_size_hook(&mut self, hook: AllocSizeHook) {
+        self.alloc_size_hook = Some(hook);
+    }
+
     pub fn get_class_symbol_table(&self) -> &SymbolTable {
         &self.class_symbol_table
     }
 
+    /// Counts of the class built last, read from its class variables.
+    pub fn get_class_metadata(&self) -> ClassMetadata {
+        ClassMetadata {
+            fields: self.class_symbol_table.count_fields(),
+            statics: self.class_symbol_table.static_count(),
+        }
+    }
+
     pub fn get_symbol_table(&self) -> &SymbolTable {
         &self.symbol_table
     }
@@ -32,7 +218,7 @@ impl VmWriter {
         self.symbol_table.increase_arguments();
     }
 
-    fn set_symbol_table(&mut self, symbol_table: SymbolTable) {
+    pub fn set_symbol_table(&mut self, symbol_table: SymbolTable) {
         self.symbol_table = symbol_table;
     }
 
@@ -44,6 +230,10 @@ impl VmWriter {
         self.class_name = value;
     }
 
+    pub fn get_warnings(&self) -> &Vec<Warning> {
+        &self.warnings
+    }
+
     pub fn get_next_id(&mut self) -> usize {
         let id = self.current_id;
         self.current_id = id + 1;
@@ -51,61 +241,91 @@ impl VmWriter {
         id
     }
 
+    fn next_while_id(&mut self) -> usize {
+        match self.options.label_numbering {
+            LabelNumbering::Class => self.get_next_id(),
+            LabelNumbering::Official => {
+                self.while_id += 1;
+                self.while_id - 1
+            }
+        }
+    }
+
+    fn next_if_id(&mut self) -> usize {
+        match self.options.label_numbering {
+            LabelNumbering::Class => self.get_next_id(),
+            LabelNumbering::Official => {
+                self.if_id += 1;
+                self.if_id - 1
+            }
+        }
+    }
+
+    /// Builds like `build`, wrapping the code in a `VmProgram`.
+    pub fn build_program(&mut self, tree: &TokenTreeItem) -> VmProgram {
+        VmProgram::new(self.build(tree))
+    }
+
     pub fn build(&mut self, tree: &TokenTreeItem) -> Vec<String> {
+        let mut result = Vec::new();
+        self.build_into(tree, &mut result);
+
+        result
+    }
+
+    /// Appends the code of `tree` to `out`. Every level of the tree writes into
+    /// the same buffer, instead of returning its own lines to be copied into
+    /// the caller's.
+    pub fn build_into(&mut self, tree: &TokenTreeItem, out: &mut Vec<String>) {
         let group = tree.get_name();
 
         if group.is_none() {
-            return Vec::new();
+            return;
         }
 
         let group = group.as_ref().unwrap().as_str();
 
         match group {
-            "expression" => self.build_expression(tree),
-            "term" => self.build_term(tree),
-            "statements" => self.build_statements(tree),
-            "letStatement" => self.build_let(tree),
-            "returnStatement" => self.build_return(tree),
-            "doStatement" => self.build_do(tree),
-            "whileStatement" => self.build_while(tree),
-            "ifStatement" => self.build_if(tree),
-            "expressionList" => self.build_expression_list(tree),
-            "class" => self.build_class(tree),
-            "classVarDec" => {
-                self.build_class_var_dec(tree);
-                Vec::new()
-            }
-            "subroutineDec" => self.build_subroutine_dec(tree),
+            "expression" => self.build_expression(tree, out),
+            "term" => self.build_term(tree, out),
+            "statements" => self.build_statements(tree, out),
+            "letStatement" => self.build_let(tree, out),
+            "returnStatement" => self.build_return(tree, out),
+            "doStatement" => self.build_do(tree, out),
+            "whileStatement" => self.build_while(tree, out),
+            "ifStatement" => self.build_if(tree, out),
+            "expressionList" => self.build_expression_list(tree, out),
+            "class" => self.build_class(tree, out),
+            "classVarDec" => self.build_class_var_dec(tree),
+            "subroutineDec" => self.build_subroutine_dec(tree, out),
             "parameterList" => {
                 let symbol_table = self.get_class_symbol_table();
                 let symbol_table = self.build_parameter_list(tree, symbol_table);
 
                 self.set_symbol_table(symbol_table);
-
-                Vec::new()
             }
             "varDec" => {
                 let symbol_table = self.get_symbol_table();
                 let symbol_table = self.build_var_dec(tree, symbol_table);
 
                 self.set_symbol_table(symbol_table);
-
-                Vec::new()
             }
-            "subroutineBody" => self.build_subroutine_body(tree),
-            value => panic!(format!("Unexpected token: {}", value)),
+            "subroutineBody" => self.build_subroutine_body(tree, out),
+            value if self.statement_registry.find_by_name(value).is_some() => {
+                let extension = self.statement_registry.find_by_name(value).unwrap();
+                out.extend(extension.write(self, tree));
+            }
+            value => panic!("Unexpected token: {}", value),
         }
     }
 
-    fn build_class(&mut self, tree: &TokenTreeItem) -> Vec<String> {
+    fn build_class(&mut self, tree: &TokenTreeItem, result: &mut Vec<String>) {
         VmWriter::validate_name(tree, "class");
 
         if tree.get_nodes().len() <= 4 {
-            return Vec::new();
+            return;
         }
 
-        let mut result = Vec::new();
-
         let class_name = tree
             .get_nodes()
             .get(1)
@@ -120,36 +340,41 @@ impl VmWriter {
 
         while tree.get_nodes().len() > next_item + 1 {
             let item = tree.get_nodes().get(next_item).unwrap();
-            result.extend(self.build(item));
+            self.build_into(item, result);
 
             next_item += 1;
         }
-
-        result
     }
 
-    fn build_subroutine_dec(&mut self, tree: &TokenTreeItem) -> Vec<String> {
+    fn build_subroutine_dec(&mut self, tree: &TokenTreeItem, result: &mut Vec<String>) {
         VmWriter::validate_name(tree, "subroutineDec");
 
-        let mut result = Vec::new();
-
         let routine_type = tree
             .get_nodes()
-            .get(0)
+            .first()
             .unwrap()
             .get_item()
             .as_ref()
             .unwrap()
             .get_value();
 
-        let name = tree
+        let return_type = tree
             .get_nodes()
-            .get(2)
+            .get(1)
             .unwrap()
             .get_item()
             .as_ref()
             .unwrap()
             .get_value();
+
+        let name_item = tree
+            .get_nodes()
+            .get(2)
+            .unwrap()
+            .get_item()
+            .as_ref()
+            .unwrap();
+        let name = name_item.get_value();
         let arguments = tree.get_nodes().get(4).unwrap();
         let body = tree.get_nodes().get(6).unwrap();
 
@@ -167,19 +392,51 @@ impl VmWriter {
             var_dec_item += 1;
         }
 
+        let hoisted_strings = if self.options.dedup_strings {
+            VmWriter::collect_duplicate_strings(body)
+        } else {
+            Vec::new()
+        };
+        self.string_locals.clear();
+        self.while_id = 0;
+        self.if_id = 0;
+
+        let count_locals = count_fields + hoisted_strings.len();
+
+        if let Some(max_locals) = self.options.max_locals {
+            if count_locals > max_locals {
+                self.warnings.push(Warning::new(
+                    format!(
+                        "{}.{} declares {} locals, more than the limit of {}",
+                        self.get_class_name(),
+                        name,
+                        count_locals,
+                        max_locals
+                    )
+                    .as_str(),
+                    name_item.get_position(),
+                ));
+            }
+        }
+
         result.push(format!(
             "function {}.{} {}",
             self.get_class_name(),
             name,
-            count_fields
+            count_locals
         ));
 
         match routine_type.as_str() {
             "constructor" => {
-                result.push(format!(
-                    "push constant {}",
-                    self.get_class_symbol_table().count_fields()
-                ));
+                // The OS `Memory.alloc` rejects a size of 0, so a class
+                // without fields still takes one word.
+                let fields = self.get_class_symbol_table().count_fields();
+                let size = match &self.alloc_size_hook {
+                    Some(hook) => hook(self.get_class_name(), fields),
+                    None => fields.max(1),
+                };
+
+                result.push(format!("push constant {}", size));
                 result.push(String::from("call Memory.alloc 1"));
                 result.push(String::from("pop pointer 0"));
             }
@@ -188,41 +445,132 @@ impl VmWriter {
                 result.push(String::from("push argument 0"));
                 result.push(String::from("pop pointer 0"));
             }
-            v => panic!(format!("Invalid routine type: {}", v)),
+            v => panic!("Invalid routine type: {}", v),
         }
 
-        result.extend(self.build(arguments));
+        self.build_into(arguments, result);
 
         if routine_type.as_str() == "method" {
             self.increase_argument_position();
         }
 
-        result.extend(self.build(body));
+        for (i, value) in hoisted_strings.iter().enumerate() {
+            result.extend(self.build_string(value));
+            result.push(format!("pop local {}", count_fields + i));
+            self.string_locals.insert(value.clone(), count_fields + i);
+        }
+
+        self.build_into(body, result);
 
-        result
+        if routine_type.as_str() == "constructor"
+            && self.options.auto_return_this
+            && !VmWriter::ends_with_return_this(body)
+        {
+            result.push(String::from("push pointer 0"));
+            result.extend(self.build_return_command());
+        }
+
+        // A void subroutine may end without `return;` (`function void noop() {}`),
+        // but the VM needs one to hand control back.
+        if return_type == "void" && !VmWriter::ends_with_return(body) {
+            result.push(String::from("push constant 0"));
+            result.extend(self.build_return_command());
+        }
+
+        if self.options.end_function_marker {
+            result.push(self.build_comment(
+                format!("end function {}.{}", self.get_class_name(), name).as_str(),
+            ));
+        }
     }
 
-    fn build_subroutine_body(&mut self, tree: &TokenTreeItem) -> Vec<String> {
-        VmWriter::validate_name(tree, "subroutineBody");
+    /// String literals found more than once in `tree`, in order of appearance.
+    fn collect_duplicate_strings(tree: &TokenTreeItem) -> Vec<String> {
+        fn collect(tree: &TokenTreeItem, counts: &mut Vec<(String, usize)>) {
+            if let Some(item) = tree.get_item() {
+                if item.get_type() == TokenType::String {
+                    match counts
+                        .iter_mut()
+                        .find(|(value, _)| *value == item.get_value())
+                    {
+                        Some((_, count)) => *count += 1,
+                        None => counts.push((item.get_value(), 1)),
+                    }
+                }
+            }
 
-        let mut result = Vec::new();
+            for node in tree.get_nodes() {
+                collect(node, counts);
+            }
+        }
+
+        let mut counts = Vec::new();
+        collect(tree, &mut counts);
+
+        counts
+            .into_iter()
+            .filter(|(_, count)| *count > 1)
+            .map(|(value, _)| value)
+            .collect()
+    }
+
+    fn last_statement(body: &TokenTreeItem) -> Option<&TokenTreeItem> {
+        let nodes = body.get_nodes();
+        let statements = nodes.get(nodes.len() - 2).unwrap();
+
+        statements.get_nodes().last()
+    }
+
+    fn ends_with_return(body: &TokenTreeItem) -> bool {
+        VmWriter::last_statement(body)
+            .is_some_and(|statement| statement.get_name().as_deref() == Some("returnStatement"))
+    }
+
+    fn ends_with_return_this(body: &TokenTreeItem) -> bool {
+        let statement = match VmWriter::last_statement(body) {
+            Some(statement) => statement,
+            None => return false,
+        };
+
+        if statement.get_name().as_deref() != Some("returnStatement")
+            || statement.get_nodes().len() != 3
+        {
+            return false;
+        }
+
+        let expression = statement.get_nodes().get(1).unwrap();
+        if expression.get_nodes().len() != 1 {
+            return false;
+        }
+
+        let term = expression.get_nodes().first().unwrap();
+        if term.get_nodes().len() != 1 {
+            return false;
+        }
+
+        match term.get_nodes().first().unwrap().get_item() {
+            Some(item) => item.get_type() == TokenType::Keyword && item.get_value() == "this",
+            None => false,
+        }
+    }
+
+    fn build_subroutine_body(&mut self, tree: &TokenTreeItem, result: &mut Vec<String>) {
+        VmWriter::validate_name(tree, "subroutineBody");
 
         let mut next_item = 1;
 
         while tree.get_nodes().len() > next_item + 1 {
             let item = tree.get_nodes().get(next_item).unwrap();
-            result.extend(self.build(item));
+            self.build_into(item, result);
             next_item += 1;
         }
-
-        result
     }
     fn build_class_var_dec(&mut self, tree: &TokenTreeItem) {
         VmWriter::validate_name(tree, "classVarDec");
 
         let symbol_type = tree
             .get_nodes()
-            .get(0)
+            .first()
             .unwrap()
             .get_item()
             .as_ref()
@@ -275,7 +623,7 @@ impl VmWriter {
         let mut symbol_table = symbol_table.clone();
 
         let symbol_type = "argument";
-        let kind = tree.get_nodes().get(0);
+        let kind = tree.get_nodes().first();
 
         if kind.is_none() {
             return symbol_table;
@@ -362,25 +710,80 @@ impl VmWriter {
         symbol_table
     }
 
-    fn build_expression(&mut self, tree: &TokenTreeItem) -> Vec<String> {
+    fn build_expression(&mut self, tree: &TokenTreeItem, result: &mut Vec<String>) {
         VmWriter::validate_name(tree, "expression");
 
-        let mut result = Vec::new();
+        if self.options.fold_constants {
+            if let Some(constant) = fold_expression(tree) {
+                result.extend(VmWriter::build_constant(constant));
+                return;
+            }
+        }
 
-        let term = tree.get_nodes().get(0).unwrap();
-        result.extend(self.build(term));
+        let term = tree.get_nodes().first().unwrap();
+        self.build_into(term, result);
 
         let mut i = 1;
 
         while i < tree.get_nodes().len() {
             let term = tree.get_nodes().get(i + 1).unwrap();
-            result.extend(self.build(term));
-
             let op = tree.get_nodes().get(i).unwrap();
-            result.push(VmWriter::build_expression_op(op));
+
+            match self.build_shift(op, term) {
+                Some(shift) => result.extend(shift),
+                None => {
+                    self.build_into(term, result);
+                    result.push(VmWriter::build_expression_op(op));
+                }
+            }
 
             i += 2;
         }
+    }
+
+    fn build_shift(&self, op: &TokenTreeItem, term: &TokenTreeItem) -> Option<Vec<String>> {
+        if !self.options.target.supports_shift() {
+            return None;
+        }
+
+        let command = match op.get_item().as_ref()?.get_value().as_str() {
+            "*" => "shl",
+            "/" => "shr",
+            _ => return None,
+        };
+
+        let amount = shift_amount(term)?;
+
+        Some(vec![
+            format!("push constant {}", amount),
+            String::from(command),
+        ])
+    }
+
+    fn build_constant(constant: Constant) -> Vec<String> {
+        let mut result = Vec::new();
+
+        match constant {
+            Constant::Boolean(value) => {
+                result.push(String::from("push constant 0"));
+
+                if value {
+                    result.push(String::from("not"));
+                }
+            }
+            // -32768 has no positive counterpart to push, so it is built as -32767 - 1
+            Constant::Integer(i16::MIN) => {
+                result.push(String::from("push constant 32767"));
+                result.push(String::from("neg"));
+                result.push(String::from("push constant 1"));
+                result.push(String::from("sub"));
+            }
+            Constant::Integer(value) if value < 0 => {
+                result.push(format!("push constant {}", -value));
+                result.push(String::from("neg"));
+            }
+            Constant::Integer(value) => result.push(format!("push constant {}", value)),
+        }
 
         result
     }
@@ -396,34 +799,35 @@ impl VmWriter {
             ">" => "gt",
             "<" => "lt",
             "=" => "eq",
-            v => panic!(format!("Invalid op on expression build: {}", v)),
+            v => panic!("Invalid op on expression build: {}", v),
         };
 
         String::from(result)
     }
 
-    fn build_term(&mut self, tree: &TokenTreeItem) -> Vec<String> {
+    fn build_term(&mut self, tree: &TokenTreeItem, result: &mut Vec<String>) {
         VmWriter::validate_name(tree, "term");
-        let mut result = Vec::new();
 
         let item = tree
             .get_nodes()
-            .get(0)
+            .first()
             .unwrap()
             .get_item()
             .as_ref()
             .unwrap();
 
         match item.get_type() {
-            TokenType::Integer => result.push(format!("push constant {}", item.get_value())),
+            // Leading zeros are dropped: `007` is `7`, never octal.
+            TokenType::Integer => result.push(format!(
+                "push constant {}",
+                item.get_value().parse::<i16>().unwrap()
+            )),
             TokenType::String => {
                 let value = item.get_value();
-                result.push(format!("push constant {}", value.len()));
-                result.push(String::from("call String.new 1"));
 
-                for c in value.chars() {
-                    result.push(format!("push constant {}", c as i32));
-                    result.push(String::from("call String.appendChar 2"));
+                match self.string_locals.get(&value) {
+                    Some(index) => result.push(format!("push local {}", index)),
+                    None => result.extend(self.build_string(&value)),
                 }
             }
             TokenType::Identifier => {
@@ -437,15 +841,15 @@ impl VmWriter {
                         result.push(self.get_symbol_table().get_push(identifier.as_str()));
 
                         let another_term = tree.get_nodes().get(2).unwrap();
-                        result.extend(self.build(another_term));
+                        self.build_into(another_term, result);
                         result.push(String::from("add"));
                         result.push(String::from("pop pointer 1"));
                         result.push(String::from("push that 0"));
                     } else {
-                        result.extend(self.build_subroutine_call(tree, "", 0));
+                        self.build_subroutine_call(tree, "", 0, result);
                     }
                 } else if tree.get_nodes().len() == 6 {
-                    result.extend(self.build_subroutine_call(tree, identifier.as_str(), 2));
+                    self.build_subroutine_call(tree, identifier.as_str(), 2, result);
                 } else {
                     result.push(self.get_symbol_table().get_push(identifier.as_str()));
                 }
@@ -458,9 +862,12 @@ impl VmWriter {
                         result.push(String::from("push constant 0"));
                         result.push(String::from("not"));
                     }
+                    "this" if tree.get_nodes().len() == 6 => {
+                        self.build_subroutine_call(tree, "this", 2, result)
+                    }
                     "this" => result.push(String::from("push pointer 0")),
                     "null" => result.push(String::from("push constant 0")),
-                    v => panic!(format!("Invalid keywork on term build: {}", v)),
+                    v => panic!("Invalid keywork on term build: {}", v),
                 }
             }
             TokenType::Symbol => {
@@ -468,46 +875,55 @@ impl VmWriter {
                 match value.as_str() {
                     "-" => {
                         let another_term = tree.get_nodes().get(1).unwrap();
-                        result.extend(self.build(another_term));
+                        self.build_into(another_term, result);
                         result.push(String::from("neg"))
                     }
                     "~" => {
                         let another_term = tree.get_nodes().get(1).unwrap();
-                        result.extend(self.build(another_term));
+                        self.build_into(another_term, result);
                         result.push(String::from("not"))
                     }
                     "(" => {
                         let another_term = tree.get_nodes().get(1).unwrap();
 
-                        result.extend(self.build(another_term));
+                        self.build_into(another_term, result);
                     }
-                    v => panic!(format!("Invalid symbol on term build: {}", v)),
+                    v => panic!("Invalid symbol on term build: {}", v),
                 }
             }
-            v => panic!(format!("Unexpected term type: {:?}", v)),
+            v => panic!("Unexpected term type: {:?}", v),
+        }
+    }
+
+    fn build_string(&self, value: &str) -> Vec<String> {
+        let mut result = Vec::new();
+        let string_class = self.options.string_class.as_str();
+
+        result.push(format!("push constant {}", value.len()));
+        result.push(format!("call {}.new 1", string_class));
+
+        for c in value.chars() {
+            result.push(format!("push constant {}", c as i32));
+            result.push(format!("call {}.appendChar 2", string_class));
         }
 
         result
     }
 
-    fn build_statements(&mut self, tree: &TokenTreeItem) -> Vec<String> {
+    fn build_statements(&mut self, tree: &TokenTreeItem, result: &mut Vec<String>) {
         VmWriter::validate_name(tree, "statements");
-        let mut result = Vec::new();
 
         for node in tree.get_nodes() {
-            result.extend(self.build(node));
+            self.build_into(node, result);
         }
-
-        result
     }
 
-    fn build_let(&mut self, tree: &TokenTreeItem) -> Vec<String> {
+    fn build_let(&mut self, tree: &TokenTreeItem, result: &mut Vec<String>) {
         VmWriter::validate_name(tree, "letStatement");
-        let mut result = Vec::new();
 
         if tree.get_nodes().len() == 5 {
             let expression = tree.get_nodes().get(3).unwrap();
-            result.extend(self.build(expression));
+            self.build_into(expression, result);
 
             let identifier = tree
                 .get_nodes()
@@ -532,12 +948,12 @@ impl VmWriter {
             result.push(self.get_symbol_table().get_push(identifier.as_str()));
 
             let expression = tree.get_nodes().get(3).unwrap();
-            result.extend(self.build(expression));
+            self.build_into(expression, result);
 
             result.push(String::from("add"));
 
             let expression = tree.get_nodes().get(6).unwrap();
-            result.extend(self.build(expression));
+            self.build_into(expression, result);
 
             result.push(String::from("pop temp 0"));
             result.push(String::from("pop pointer 1"));
@@ -546,29 +962,39 @@ impl VmWriter {
         } else {
             panic!("Invalid number of arguments on build let statement");
         }
-
-        result
     }
 
-    fn build_return(&mut self, tree: &TokenTreeItem) -> Vec<String> {
+    fn build_return(&mut self, tree: &TokenTreeItem, result: &mut Vec<String>) {
         VmWriter::validate_name(tree, "returnStatement");
-        let mut result = Vec::new();
 
         if tree.get_nodes().len() == 3 {
             let expression = tree.get_nodes().get(1).unwrap();
-            result.extend(self.build(expression));
+            self.build_into(expression, result);
         } else {
             result.push(String::from("push constant 0"));
         }
 
+        result.extend(self.build_return_command());
+    }
+
+    fn build_comment(&self, text: &str) -> String {
+        format!("{} {}", self.options.comment_prefix, text)
+    }
+
+    fn build_return_command(&self) -> Vec<String> {
+        let mut result = Vec::new();
+
+        if self.options.return_convention == ReturnConvention::CallerCleanup {
+            result.push(self.build_comment("epilogue: caller cleans up arguments"));
+        }
+
         result.push(String::from("return"));
 
         result
     }
 
-    fn build_do(&mut self, tree: &TokenTreeItem) -> Vec<String> {
+    fn build_do(&mut self, tree: &TokenTreeItem, result: &mut Vec<String>) {
         VmWriter::validate_name(tree, "doStatement");
-        let mut result = Vec::new();
 
         let mut base_index: usize = 1;
 
@@ -585,10 +1011,8 @@ impl VmWriter {
             String::new()
         };
 
-        result.extend(self.build_subroutine_call(tree, class_name.as_str(), base_index));
+        self.build_subroutine_call(tree, class_name.as_str(), base_index, result);
         result.push(String::from("pop temp 0"));
-
-        result
     }
 
     fn build_subroutine_call(
@@ -596,16 +1020,17 @@ impl VmWriter {
         tree: &TokenTreeItem,
         identifier: &str,
         base_item: usize,
-    ) -> Vec<String> {
-        let mut result = Vec::new();
-
+        result: &mut Vec<String>,
+    ) {
         let mut name = String::from(identifier);
 
         let another_identifier = tree.get_nodes().get(base_item).unwrap();
-        let another_identifier = another_identifier.get_item().as_ref().unwrap().get_value();
+        let another_identifier = another_identifier.get_item().as_ref().unwrap();
+        let position = another_identifier.get_position();
+        let another_identifier = another_identifier.get_value();
 
         let expression_list = tree.get_nodes().get(base_item + 2).unwrap();
-        let mut count_arguments = (expression_list.get_nodes().len() + 1) / 2;
+        let mut count_arguments = expression_list.get_nodes().len().div_ceil(2);
 
         if self.get_symbol_table().contains(identifier) {
             result.push(self.get_symbol_table().get_push(identifier));
@@ -613,13 +1038,26 @@ impl VmWriter {
             count_arguments += 1;
         }
 
-        if identifier.len() == 0 {
+        if identifier.is_empty() || identifier == "this" {
             name = self.get_class_name().clone();
             result.push(String::from("push pointer 0"));
             count_arguments += 1;
         }
 
-        result.extend(self.build(expression_list));
+        self.build_into(expression_list, result);
+
+        if let Some(max_arguments) = self.options.max_arguments {
+            if count_arguments > max_arguments {
+                self.warnings.push(Warning::new(
+                    format!(
+                        "call to {}.{} passes {} arguments, more than the limit of {}",
+                        name, another_identifier, count_arguments, max_arguments
+                    )
+                    .as_str(),
+                    position,
+                ));
+            }
+        }
 
         result.push(format!(
             "call {}.{} {}",
@@ -627,46 +1065,40 @@ impl VmWriter {
             another_identifier,
             count_arguments
         ));
-
-        result
     }
 
-    fn build_while(&mut self, tree: &TokenTreeItem) -> Vec<String> {
+    fn build_while(&mut self, tree: &TokenTreeItem, result: &mut Vec<String>) {
         VmWriter::validate_name(tree, "whileStatement");
-        let mut result = Vec::new();
-        let count = self.get_next_id();
+        let count = self.next_while_id();
 
         result.push(format!("label WHILE_EXP{}", count));
 
         let expression = tree.get_nodes().get(2).unwrap();
-        result.extend(self.build(expression));
+        self.build_into(expression, result);
 
         result.push(String::from("not"));
         result.push(format!("if-goto WHILE_END{}", count));
 
         let expression = tree.get_nodes().get(5).unwrap();
-        result.extend(self.build(expression));
+        self.build_into(expression, result);
 
         result.push(format!("goto WHILE_EXP{}", count));
         result.push(format!("label WHILE_END{}", count));
-
-        result
     }
 
-    fn build_if(&mut self, tree: &TokenTreeItem) -> Vec<String> {
+    fn build_if(&mut self, tree: &TokenTreeItem, result: &mut Vec<String>) {
         VmWriter::validate_name(tree, "ifStatement");
-        let mut result = Vec::new();
-        let count = self.get_next_id();
+        let count = self.next_if_id();
 
         let expression = tree.get_nodes().get(2).unwrap();
-        result.extend(self.build(expression));
+        self.build_into(expression, result);
 
         result.push(format!("if-goto IF_TRUE{}", count));
         result.push(format!("goto IF_FALSE{}", count));
         result.push(format!("label IF_TRUE{}", count));
 
         let expression = tree.get_nodes().get(5).unwrap();
-        result.extend(self.build(expression));
+        self.build_into(expression, result);
 
         if tree.get_nodes().len() == 7 {
             result.push(format!("label IF_FALSE{}", count));
@@ -675,45 +1107,46 @@ impl VmWriter {
             result.push(format!("label IF_FALSE{}", count));
 
             let expression = tree.get_nodes().get(9).unwrap();
-            result.extend(self.build(expression));
+            self.build_into(expression, result);
 
             result.push(format!("label IF_END{}", count));
         }
-
-        result
     }
 
-    fn build_expression_list(&mut self, tree: &TokenTreeItem) -> Vec<String> {
+    fn build_expression_list(&mut self, tree: &TokenTreeItem, result: &mut Vec<String>) {
         VmWriter::validate_name(tree, "expressionList");
-        let mut result = Vec::new();
 
         let mut i = 0;
 
         while i < tree.get_nodes().len() {
-            result.extend(self.build(tree.get_nodes().get(i).unwrap()));
+            self.build_into(tree.get_nodes().get(i).unwrap(), result);
             i += 2;
         }
-
-        result
     }
 
     fn validate_name(item: &TokenTreeItem, name: &str) {
         let item_name = item.get_name().as_ref();
 
         if item_name.is_none() {
-            panic!(format!("Missing name on TokenTreeItem. Expected {}", name));
+            panic!("Missing name on TokenTreeItem. Expected {}", name);
         }
 
         let item_name = item_name.unwrap();
         if item_name != name {
-            panic!(format!(
+            panic!(
                 "Invalid name on TokenTreeItem. Expected {}. Found {}.",
                 name, item_name
-            ));
+            );
         }
     }
 }
 
+impl Default for VmWriter {
+    fn default() -> Self {
+        VmWriter::new()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -725,37 +1158,147 @@ mod tests {
     #[test]
     fn build_expression_with_constants() {
         let tokenizer = Tokenizer::new("1 + 4 - 3");
-        let tree = Expression::build(&tokenizer);
+        let tree = Expression::build(&tokenizer).unwrap();
 
         let mut writer = VmWriter::new();
         let code: Vec<String> = writer.build(&tree);
 
-        assert_eq!(code.get(0).unwrap(), "push constant 1");
+        assert_eq!(code.first().unwrap(), "push constant 1");
         assert_eq!(code.get(1).unwrap(), "push constant 4");
         assert_eq!(code.get(2).unwrap(), "add");
         assert_eq!(code.get(3).unwrap(), "push constant 3");
         assert_eq!(code.get(4).unwrap(), "sub");
     }
 
+    #[test]
+    fn build_expression_with_four_operands() {
+        let tokenizer = Tokenizer::new("a + b + c + d");
+        let tree = Expression::build(&tokenizer).unwrap();
+
+        let mut writer = VmWriter::new();
+        let mut symbol_table = SymbolTable::new();
+        symbol_table.add("var", "int", "a");
+        symbol_table.add("var", "int", "b");
+        symbol_table.add("var", "int", "c");
+        symbol_table.add("var", "int", "d");
+        writer.set_symbol_table(symbol_table);
+        let code: Vec<String> = writer.build(&tree);
+
+        assert_eq!(
+            code,
+            vec![
+                "push local 0",
+                "push local 1",
+                "add",
+                "push local 2",
+                "add",
+                "push local 3",
+                "add",
+            ]
+        );
+    }
+
     #[test]
     fn build_expression_with_parenthesis() {
         let tokenizer = Tokenizer::new("1 + (4 * 3)");
-        let tree = Expression::build(&tokenizer);
+        let tree = Expression::build(&tokenizer).unwrap();
 
         let mut writer = VmWriter::new();
         let code: Vec<String> = writer.build(&tree);
 
-        assert_eq!(code.get(0).unwrap(), "push constant 1");
+        assert_eq!(code.first().unwrap(), "push constant 1");
         assert_eq!(code.get(1).unwrap(), "push constant 4");
         assert_eq!(code.get(2).unwrap(), "push constant 3");
         assert_eq!(code.get(3).unwrap(), "call Math.multiply 2");
         assert_eq!(code.get(4).unwrap(), "add");
     }
 
+    #[test]
+    fn build_expression_with_boolean_folding() {
+        let tokenizer = Tokenizer::new("true & false");
+        let tree = Expression::build(&tokenizer).unwrap();
+
+        let mut writer = VmWriter::with_options(WriterOptions {
+            fold_constants: true,
+            ..WriterOptions::default()
+        });
+        let code: Vec<String> = writer.build(&tree);
+
+        assert_eq!(code, vec!["push constant 0"]);
+    }
+
+    #[test]
+    fn build_expression_without_boolean_folding() {
+        let tokenizer = Tokenizer::new("true & false");
+        let tree = Expression::build(&tokenizer).unwrap();
+
+        let mut writer = VmWriter::new();
+        let code: Vec<String> = writer.build(&tree);
+
+        assert_eq!(
+            code,
+            vec!["push constant 0", "not", "push constant 0", "and"]
+        );
+    }
+
+    #[test]
+    fn build_expression_with_integer_folding() {
+        let tokenizer = Tokenizer::new("(-7) / 2");
+        let tree = Expression::build(&tokenizer).unwrap();
+
+        let mut writer = VmWriter::with_options(WriterOptions {
+            fold_constants: true,
+            ..WriterOptions::default()
+        });
+        let code: Vec<String> = writer.build(&tree);
+
+        assert_eq!(code, vec!["push constant 3", "neg"]);
+    }
+
+    #[test]
+    fn build_expression_with_shift_target() {
+        let tokenizer = Tokenizer::new("x * 4 / 2 * 3");
+        let tree = Expression::build(&tokenizer).unwrap();
+
+        let mut writer = VmWriter::with_options(WriterOptions {
+            target: Target::ShiftVm,
+            ..WriterOptions::default()
+        });
+        let mut symbol_table = SymbolTable::new();
+        symbol_table.add("var", "int", "x");
+        writer.set_symbol_table(symbol_table);
+        let code: Vec<String> = writer.build(&tree);
+
+        assert_eq!(code.len(), 7);
+        assert_eq!(code.first().unwrap(), "push local 0");
+        assert_eq!(code.get(1).unwrap(), "push constant 2");
+        assert_eq!(code.get(2).unwrap(), "shl");
+        assert_eq!(code.get(3).unwrap(), "push constant 1");
+        assert_eq!(code.get(4).unwrap(), "shr");
+        assert_eq!(code.get(5).unwrap(), "push constant 3");
+        assert_eq!(code.get(6).unwrap(), "call Math.multiply 2");
+    }
+
+    #[test]
+    fn build_expression_without_shift_target() {
+        let tokenizer = Tokenizer::new("x * 4");
+        let tree = Expression::build(&tokenizer).unwrap();
+
+        let mut writer = VmWriter::new();
+        let mut symbol_table = SymbolTable::new();
+        symbol_table.add("var", "int", "x");
+        writer.set_symbol_table(symbol_table);
+        let code: Vec<String> = writer.build(&tree);
+
+        assert_eq!(code.len(), 3);
+        assert_eq!(code.get(1).unwrap(), "push constant 4");
+        assert_eq!(code.get(2).unwrap(), "call Math.multiply 2");
+    }
+
     #[test]
     fn build_let_with_array() {
         let tokenizer = Tokenizer::new("let a[x + 1] = 5;");
-        let tree = Statement::build(&tokenizer);
+        let tree = Statement::build(&tokenizer).unwrap();
 
         let mut symbol_table = SymbolTable::new();
         symbol_table.add("var", "int", "x");
@@ -765,7 +1308,7 @@ mod tests {
         writer.set_symbol_table(symbol_table);
         let code: Vec<String> = writer.build(&tree);
 
-        assert_eq!(code.get(0).unwrap(), "push local 1");
+        assert_eq!(code.first().unwrap(), "push local 1");
         assert_eq!(code.get(1).unwrap(), "push local 0");
         assert_eq!(code.get(2).unwrap(), "push constant 1");
         assert_eq!(code.get(3).unwrap(), "add");
@@ -780,25 +1323,84 @@ mod tests {
     }
 
     #[test]
-    fn build_let_with_two_arrays() {
-        let tokenizer = Tokenizer::new("let a[x] = a[5];");
-        let tree = Statement::build(&tokenizer);
+    fn build_let_with_method_call_index() {
+        let tokenizer = Tokenizer::new("let a[obj.get()] = 1;");
+        let tree = Statement::build(&tokenizer).unwrap();
 
         let mut symbol_table = SymbolTable::new();
-        symbol_table.add("var", "int", "x");
         symbol_table.add("var", "Array", "a");
+        symbol_table.add("var", "Counter", "obj");
 
         let mut writer = VmWriter::new();
         writer.set_symbol_table(symbol_table);
         let code: Vec<String> = writer.build(&tree);
 
-        assert_eq!(code.get(0).unwrap(), "push local 1");
-        assert_eq!(code.get(1).unwrap(), "push local 0");
-        assert_eq!(code.get(2).unwrap(), "add");
+        assert_eq!(
+            code,
+            vec![
+                "push local 0",
+                "push local 1",
+                "call Counter.get 1",
+                "add",
+                "push constant 1",
+                "pop temp 0",
+                "pop pointer 1",
+                "push temp 0",
+                "pop that 0",
+            ]
+        );
+    }
 
-        assert_eq!(code.get(3).unwrap(), "push local 1");
-        assert_eq!(code.get(4).unwrap(), "push constant 5");
-        assert_eq!(code.get(5).unwrap(), "add");
+    #[test]
+    fn build_let_with_complex_array_index() {
+        let tokenizer = Tokenizer::new("let x = a[-i + 1];");
+        let tree = Statement::build(&tokenizer).unwrap();
+
+        let mut symbol_table = SymbolTable::new();
+        symbol_table.add("var", "int", "x");
+        symbol_table.add("var", "Array", "a");
+        symbol_table.add("var", "int", "i");
+
+        let mut writer = VmWriter::new();
+        writer.set_symbol_table(symbol_table);
+        let code: Vec<String> = writer.build(&tree);
+
+        assert_eq!(
+            code,
+            vec![
+                "push local 1",
+                "push local 2",
+                "neg",
+                "push constant 1",
+                "add",
+                "add",
+                "pop pointer 1",
+                "push that 0",
+                "pop local 0",
+            ]
+        );
+    }
+
+    #[test]
+    fn build_let_with_two_arrays() {
+        let tokenizer = Tokenizer::new("let a[x] = a[5];");
+        let tree = Statement::build(&tokenizer).unwrap();
+
+        let mut symbol_table = SymbolTable::new();
+        symbol_table.add("var", "int", "x");
+        symbol_table.add("var", "Array", "a");
+
+        let mut writer = VmWriter::new();
+        writer.set_symbol_table(symbol_table);
+        let code: Vec<String> = writer.build(&tree);
+
+        assert_eq!(code.first().unwrap(), "push local 1");
+        assert_eq!(code.get(1).unwrap(), "push local 0");
+        assert_eq!(code.get(2).unwrap(), "add");
+
+        assert_eq!(code.get(3).unwrap(), "push local 1");
+        assert_eq!(code.get(4).unwrap(), "push constant 5");
+        assert_eq!(code.get(5).unwrap(), "add");
         assert_eq!(code.get(6).unwrap(), "pop pointer 1");
         assert_eq!(code.get(7).unwrap(), "push that 0");
 
@@ -815,13 +1417,13 @@ mod tests {
         let mut symbol_table = SymbolTable::new();
         symbol_table.add("var", "int", "x");
 
-        let tree = Statement::build(&tokenizer);
+        let tree = Statement::build(&tokenizer).unwrap();
 
         let mut writer = VmWriter::new();
         writer.set_symbol_table(symbol_table);
         let code: Vec<String> = writer.build(&tree);
 
-        assert_eq!(code.get(0).unwrap(), "push constant 2");
+        assert_eq!(code.first().unwrap(), "push constant 2");
         assert_eq!(code.get(1).unwrap(), "push constant 2");
         assert_eq!(code.get(2).unwrap(), "add");
         assert_eq!(code.get(3).unwrap(), "pop local 0");
@@ -834,18 +1436,38 @@ mod tests {
         let mut symbol_table = SymbolTable::new();
         symbol_table.add("var", "int", "x");
 
-        let tree = Statement::build(&tokenizer);
+        let tree = Statement::build(&tokenizer).unwrap();
 
         let mut writer = VmWriter::new();
         writer.set_symbol_table(symbol_table);
         let code: Vec<String> = writer.build(&tree);
 
-        assert_eq!(code.get(0).unwrap(), "push local 0");
+        assert_eq!(code.first().unwrap(), "push local 0");
         assert_eq!(code.get(1).unwrap(), "push constant 2");
         assert_eq!(code.get(2).unwrap(), "add");
         assert_eq!(code.get(3).unwrap(), "pop local 0");
     }
 
+    #[test]
+    fn build_let_with_comparison() {
+        let tokenizer = Tokenizer::new("let done = x < y;");
+        let tree = Statement::build(&tokenizer).unwrap();
+
+        let mut symbol_table = SymbolTable::new();
+        symbol_table.add("var", "int", "x");
+        symbol_table.add("var", "int", "y");
+        symbol_table.add("var", "boolean", "done");
+
+        let mut writer = VmWriter::new();
+        writer.set_symbol_table(symbol_table);
+        let code: Vec<String> = writer.build(&tree);
+
+        assert_eq!(
+            code,
+            vec!["push local 0", "push local 1", "lt", "pop local 2"]
+        );
+    }
+
     #[test]
     fn build_let_with_string() {
         let tokenizer = Tokenizer::new("let name = \"Ola\";");
@@ -853,13 +1475,13 @@ mod tests {
         let mut symbol_table = SymbolTable::new();
         symbol_table.add("var", "String", "name");
 
-        let tree = Statement::build(&tokenizer);
+        let tree = Statement::build(&tokenizer).unwrap();
 
         let mut writer = VmWriter::new();
         writer.set_symbol_table(symbol_table);
         let code: Vec<String> = writer.build(&tree);
 
-        assert_eq!(code.get(0).unwrap(), "push constant 3");
+        assert_eq!(code.first().unwrap(), "push constant 3");
         assert_eq!(code.get(1).unwrap(), "call String.new 1");
         assert_eq!(code.get(2).unwrap(), "push constant 79");
         assert_eq!(code.get(3).unwrap(), "call String.appendChar 2");
@@ -870,15 +1492,113 @@ mod tests {
         assert_eq!(code.get(8).unwrap(), "pop local 0");
     }
 
+    #[test]
+    fn build_let_with_empty_string() {
+        let tokenizer = Tokenizer::new("let s = \"\";");
+
+        let mut symbol_table = SymbolTable::new();
+        symbol_table.add("var", "String", "s");
+
+        let tree = Statement::build(&tokenizer).unwrap();
+
+        let mut writer = VmWriter::new();
+        writer.set_symbol_table(symbol_table);
+        let code: Vec<String> = writer.build(&tree);
+
+        assert_eq!(
+            code,
+            vec!["push constant 0", "call String.new 1", "pop local 0"]
+        );
+    }
+
+    #[test]
+    fn build_let_with_spaced_unary_minus() {
+        let compile = |source: &str| {
+            let tokenizer = Tokenizer::new(source);
+            let tree = Statement::build(&tokenizer).unwrap();
+
+            let mut symbol_table = SymbolTable::new();
+            symbol_table.add("var", "int", "x");
+
+            let mut writer = VmWriter::new();
+            writer.set_symbol_table(symbol_table);
+
+            writer.build(&tree)
+        };
+
+        let code = compile("let x = - 5;");
+
+        assert_eq!(code, vec!["push constant 5", "neg", "pop local 0"]);
+        assert_eq!(code, compile("let x = -5;"));
+    }
+
+    #[test]
+    fn build_let_with_padded_string() {
+        let tokenizer = Tokenizer::new("let s = \"  hi  \";");
+
+        let mut symbol_table = SymbolTable::new();
+        symbol_table.add("var", "String", "s");
+
+        let tree = Statement::build(&tokenizer).unwrap();
+
+        let mut writer = VmWriter::new();
+        writer.set_symbol_table(symbol_table);
+        let code: Vec<String> = writer.build(&tree);
+
+        let chars: Vec<&String> = code
+            .iter()
+            .filter(|line| line.starts_with("push constant"))
+            .collect();
+
+        assert_eq!(
+            chars,
+            vec![
+                "push constant 6",
+                "push constant 32",
+                "push constant 32",
+                "push constant 104",
+                "push constant 105",
+                "push constant 32",
+                "push constant 32",
+            ]
+        );
+        assert_eq!(code.last().unwrap(), "pop local 0");
+    }
+
+    #[test]
+    fn build_let_with_custom_string_class() {
+        let tokenizer = Tokenizer::new("let name = \"Ok\";");
+
+        let mut symbol_table = SymbolTable::new();
+        symbol_table.add("var", "Text", "name");
+
+        let tree = Statement::build(&tokenizer).unwrap();
+
+        let mut writer = VmWriter::with_options(WriterOptions {
+            string_class: String::from("Text"),
+            ..WriterOptions::default()
+        });
+        writer.set_symbol_table(symbol_table);
+        let code: Vec<String> = writer.build(&tree);
+
+        assert_eq!(code.first().unwrap(), "push constant 2");
+        assert_eq!(code.get(1).unwrap(), "call Text.new 1");
+        assert_eq!(code.get(2).unwrap(), "push constant 79");
+        assert_eq!(code.get(3).unwrap(), "call Text.appendChar 2");
+        assert_eq!(code.get(4).unwrap(), "push constant 107");
+        assert_eq!(code.get(5).unwrap(), "call Text.appendChar 2");
+        assert_eq!(code.get(6).unwrap(), "pop local 0");
+    }
+
     #[test]
     fn build_return_false() {
         let tokenizer = Tokenizer::new("return true;");
-        let tree = Statement::build(&tokenizer);
+        let tree = Statement::build(&tokenizer).unwrap();
 
         let mut writer = VmWriter::new();
         let code: Vec<String> = writer.build(&tree);
 
-        assert_eq!(code.get(0).unwrap(), "push constant 0");
+        assert_eq!(code.first().unwrap(), "push constant 0");
         assert_eq!(code.get(1).unwrap(), "not");
         assert_eq!(code.get(2).unwrap(), "return");
     }
@@ -886,32 +1606,516 @@ mod tests {
     #[test]
     fn build_return_void() {
         let tokenizer = Tokenizer::new("return;");
-        let tree = Statement::build(&tokenizer);
+        let tree = Statement::build(&tokenizer).unwrap();
 
         let mut writer = VmWriter::new();
         let code: Vec<String> = writer.build(&tree);
 
-        assert_eq!(code.get(0).unwrap(), "push constant 0");
+        assert_eq!(code.first().unwrap(), "push constant 0");
         assert_eq!(code.get(1).unwrap(), "return");
     }
 
+    #[test]
+    fn build_return_with_conventions() {
+        let tokenizer = Tokenizer::new("return 1;");
+        let tree = Statement::build(&tokenizer).unwrap();
+
+        let code: Vec<String> = VmWriter::new().build(&tree);
+        assert_eq!(code, vec!["push constant 1", "return"]);
+
+        let mut writer = VmWriter::with_options(WriterOptions {
+            return_convention: ReturnConvention::CallerCleanup,
+            ..WriterOptions::default()
+        });
+        let code: Vec<String> = writer.build(&tree);
+        assert_eq!(
+            code,
+            vec![
+                "push constant 1",
+                "// epilogue: caller cleans up arguments",
+                "return"
+            ]
+        );
+    }
+
+    #[test]
+    fn build_constructor_with_alloc_size_hook() {
+        let source = "class Point { field int x, y; constructor Point new() { return this; } }";
+        let compile = |writer: &mut VmWriter| {
+            let tokenizer = Tokenizer::new(source);
+            let tree = ClassNode::build(&tokenizer).unwrap();
+
+            writer.build(&tree)
+        };
+
+        let code = compile(&mut VmWriter::new());
+        assert_eq!(code.get(1).unwrap(), "push constant 2");
+        assert_eq!(code.get(2).unwrap(), "call Memory.alloc 1");
+
+        let mut writer = VmWriter::new();
+        writer.set_alloc_size_hook(std::rc::Rc::new(|class_name: &str, fields: usize| {
+            assert_eq!(class_name, "Point");
+            fields + 1
+        }));
+        let code = compile(&mut writer);
+        assert_eq!(code.get(1).unwrap(), "push constant 3");
+        assert_eq!(code.get(2).unwrap(), "call Memory.alloc 1");
+    }
+
+    #[test]
+    fn build_class_metadata() {
+        let source = "class Main { field int a, b; static int c; field boolean d; function void main() { return; } }";
+        let tokenizer = Tokenizer::new(source);
+        let tree = ClassNode::build(&tokenizer).unwrap();
+        let mut writer = VmWriter::new();
+
+        writer.build(&tree);
+        let metadata = writer.get_class_metadata();
+
+        assert_eq!(metadata.get_fields(), 3);
+        assert_eq!(metadata.get_statics(), 1);
+    }
+
+    #[test]
+    fn build_class_with_semicolon_comments() {
+        let source = "class Main { function int main() { return 1; } }";
+        let tokenizer = Tokenizer::new(source);
+        let tree = ClassNode::build(&tokenizer).unwrap();
+        let mut writer = VmWriter::with_options(WriterOptions {
+            end_function_marker: true,
+            return_convention: ReturnConvention::CallerCleanup,
+            comment_prefix: String::from(";"),
+            ..WriterOptions::default()
+        });
+
+        let code: Vec<String> = writer.build(&tree);
+
+        assert_eq!(
+            code,
+            vec![
+                "function Main.main 0",
+                "push constant 1",
+                "; epilogue: caller cleans up arguments",
+                "return",
+                "; end function Main.main",
+            ]
+        );
+    }
+
     #[test]
     fn build_do_this() {
         let tokenizer = Tokenizer::new("do Memory.deAlloc(this);");
-        let tree = Statement::build(&tokenizer);
+        let tree = Statement::build(&tokenizer).unwrap();
 
         let mut writer = VmWriter::new();
         let code: Vec<String> = writer.build(&tree);
 
-        assert_eq!(code.get(0).unwrap(), "push pointer 0");
+        assert_eq!(code.first().unwrap(), "push pointer 0");
         assert_eq!(code.get(1).unwrap(), "call Memory.deAlloc 1");
         assert_eq!(code.get(2).unwrap(), "pop temp 0");
     }
 
+    #[test]
+    fn build_do_discards_void_and_non_void_results() {
+        let source = "class Main { field Array a; method void draw() { return; } method int size() { return 1; } method void run() { do Screen.clearScreen(); do Math.abs(1); do draw(); do size(); do a.dispose(); do a.get(0); return; } }";
+        let tokenizer = Tokenizer::new(source);
+        let tree = ClassNode::build(&tokenizer).unwrap();
+        let mut writer = VmWriter::new();
+
+        let code: Vec<String> = writer
+            .build_program(&tree)
+            .get_function("Main.run")
+            .unwrap()
+            .get_code()
+            .clone();
+        let calls: Vec<usize> = code
+            .iter()
+            .enumerate()
+            .filter(|(_, line)| line.starts_with("call "))
+            .map(|(i, _)| i)
+            .collect();
+
+        assert_eq!(calls.len(), 6);
+        for i in calls {
+            assert_eq!(code.get(i + 1).unwrap(), "pop temp 0", "after {}", code[i]);
+        }
+    }
+
+    #[test]
+    fn build_let_with_parenthesized_call() {
+        let compile = |statement: &str| {
+            let source = format!(
+                "class Main {{ method int f() {{ return 1; }} method void run() {{ var int x; {} return; }} }}",
+                statement
+            );
+            let tokenizer = Tokenizer::new(&source);
+            let tree = ClassNode::build(&tokenizer).unwrap();
+
+            VmWriter::new()
+                .build_program(&tree)
+                .get_function("Main.run")
+                .unwrap()
+                .get_code()
+                .clone()
+        };
+
+        let code = compile("let x = (f());");
+
+        assert_eq!(code, compile("let x = f();"));
+        assert_eq!(
+            code,
+            vec![
+                "function Main.run 1",
+                "push argument 0",
+                "pop pointer 0",
+                "push pointer 0",
+                "call Main.f 1",
+                "pop local 0",
+                "push constant 0",
+                "return",
+            ]
+        );
+    }
+
+    #[test]
+    fn build_let_with_leading_zeros() {
+        let tokenizer = Tokenizer::new("let x = 007;");
+        let tree = Statement::build(&tokenizer).unwrap();
+
+        let mut symbol_table = SymbolTable::new();
+        symbol_table.add("var", "int", "x");
+
+        let mut writer = VmWriter::new();
+        writer.set_symbol_table(symbol_table);
+        let code: Vec<String> = writer.build(&tree);
+
+        assert_eq!(code, vec!["push constant 7", "pop local 0"]);
+    }
+
+    #[test]
+    fn build_let_assigning_argument() {
+        let source = "class Main { method void grow(int size) { let size = size + 1; return; } }";
+        let tokenizer = Tokenizer::new(source);
+        let tree = ClassNode::build(&tokenizer).unwrap();
+        let mut writer = VmWriter::new();
+
+        let code: Vec<String> = writer.build(&tree);
+
+        assert_eq!(
+            code[3..7].to_vec(),
+            vec![
+                "push argument 1",
+                "push constant 1",
+                "add",
+                "pop argument 1",
+            ]
+        );
+    }
+
+    #[test]
+    fn build_into_matches_build() {
+        let source = "class Point { field int x, y; static int count; constructor Point new(int ax) { let x = ax; let count = count + 1; return this; } method int dist(Point other) { var Array a; let a = Array.new(2); let a[0] = x - other.getX(); if (a[0] < 0) { let a[0] = -a[0]; } while (y > 0) { let y = y - 1; } do Output.printString(\"done\"); return a[0]; } }";
+        let tokenizer = Tokenizer::new(source);
+        let tree = ClassNode::build(&tokenizer).unwrap();
+
+        let mut out = vec![String::from("// header")];
+        VmWriter::new().build_into(&tree, &mut out);
+
+        assert_eq!(
+            out,
+            vec![
+                "// header",
+                "function Point.new 0",
+                "push constant 2",
+                "call Memory.alloc 1",
+                "pop pointer 0",
+                "push argument 0",
+                "pop this 0",
+                "push static 0",
+                "push constant 1",
+                "add",
+                "pop static 0",
+                "push pointer 0",
+                "return",
+                "function Point.dist 1",
+                "push argument 0",
+                "pop pointer 0",
+                "push constant 2",
+                "call Array.new 1",
+                "pop local 0",
+                "push local 0",
+                "push constant 0",
+                "add",
+                "push this 0",
+                "push argument 1",
+                "call Point.getX 1",
+                "sub",
+                "pop temp 0",
+                "pop pointer 1",
+                "push temp 0",
+                "pop that 0",
+                "push local 0",
+                "push constant 0",
+                "add",
+                "pop pointer 1",
+                "push that 0",
+                "push constant 0",
+                "lt",
+                "if-goto IF_TRUE0",
+                "goto IF_FALSE0",
+                "label IF_TRUE0",
+                "push local 0",
+                "push constant 0",
+                "add",
+                "push local 0",
+                "push constant 0",
+                "add",
+                "pop pointer 1",
+                "push that 0",
+                "neg",
+                "pop temp 0",
+                "pop pointer 1",
+                "push temp 0",
+                "pop that 0",
+                "label IF_FALSE0",
+                "label WHILE_EXP1",
+                "push this 1",
+                "push constant 0",
+                "gt",
+                "not",
+                "if-goto WHILE_END1",
+                "push this 1",
+                "push constant 1",
+                "sub",
+                "pop this 1",
+                "goto WHILE_EXP1",
+                "label WHILE_END1",
+                "push constant 4",
+                "call String.new 1",
+                "push constant 100",
+                "call String.appendChar 2",
+                "push constant 111",
+                "call String.appendChar 2",
+                "push constant 110",
+                "call String.appendChar 2",
+                "push constant 101",
+                "call String.appendChar 2",
+                "call Output.printString 1",
+                "pop temp 0",
+                "push local 0",
+                "push constant 0",
+                "add",
+                "pop pointer 1",
+                "push that 0",
+                "return",
+            ]
+        );
+        assert_eq!(VmWriter::new().build(&tree), out[1..].to_vec());
+    }
+
+    #[test]
+    fn build_call_with_too_many_arguments() {
+        let arguments = vec!["1"; 40].join(", ");
+        let source = format!(
+            "class Main {{ function void main() {{ do Math.max({}); do Math.max(1, 2); return; }} }}",
+            arguments
+        );
+        let tokenizer = Tokenizer::new(&source);
+        let tree = ClassNode::build(&tokenizer).unwrap();
+        let mut writer = VmWriter::new();
+
+        let code: Vec<String> = writer.build(&tree);
+
+        assert_eq!(code.get(41).unwrap(), "call Math.max 40");
+        assert_eq!(
+            writer
+                .get_warnings()
+                .iter()
+                .map(|warning| warning.to_string())
+                .collect::<Vec<String>>(),
+            vec!["1:45: call to Math.max passes 40 arguments, more than the limit of 32"]
+        );
+
+        let mut writer = VmWriter::with_options(WriterOptions {
+            max_arguments: None,
+            ..WriterOptions::default()
+        });
+        writer.build(&tree);
+
+        assert!(writer.get_warnings().is_empty());
+    }
+
+    #[test]
+    fn build_empty_if_and_while_bodies() {
+        let source = "class Main { function void main(boolean c) { if (c) {} else {} while (c) {} return; } }";
+        let tokenizer = Tokenizer::new(source);
+        let tree = ClassNode::build(&tokenizer).unwrap();
+        let mut writer = VmWriter::new();
+
+        let code: Vec<String> = writer.build(&tree);
+
+        assert_eq!(
+            code,
+            vec![
+                "function Main.main 0",
+                "push argument 0",
+                "if-goto IF_TRUE0",
+                "goto IF_FALSE0",
+                "label IF_TRUE0",
+                "goto IF_END0",
+                "label IF_FALSE0",
+                "label IF_END0",
+                "label WHILE_EXP1",
+                "push argument 0",
+                "not",
+                "if-goto WHILE_END1",
+                "goto WHILE_EXP1",
+                "label WHILE_END1",
+                "push constant 0",
+                "return",
+            ]
+        );
+    }
+
+    #[test]
+    fn build_if_with_while_in_else() {
+        let source = "class Main { function void main(boolean a, boolean b) { if (a) { do Main.f(); } else { while (b) { do Main.g(); } } return; } }";
+        let tokenizer = Tokenizer::new(source);
+        let tree = ClassNode::build(&tokenizer).unwrap();
+        let mut writer = VmWriter::new();
+
+        let code: Vec<String> = writer.build(&tree);
+
+        assert_eq!(
+            code,
+            vec![
+                "function Main.main 0",
+                "push argument 0",
+                "if-goto IF_TRUE0",
+                "goto IF_FALSE0",
+                "label IF_TRUE0",
+                "call Main.f 0",
+                "pop temp 0",
+                "goto IF_END0",
+                "label IF_FALSE0",
+                "label WHILE_EXP1",
+                "push argument 1",
+                "not",
+                "if-goto WHILE_END1",
+                "call Main.g 0",
+                "pop temp 0",
+                "goto WHILE_EXP1",
+                "label WHILE_END1",
+                "label IF_END0",
+                "push constant 0",
+                "return",
+            ]
+        );
+
+        let labels: Vec<&String> = code
+            .iter()
+            .filter(|line| line.starts_with("label "))
+            .collect();
+        let unique: std::collections::HashSet<&&String> = labels.iter().collect();
+        assert_eq!(unique.len(), labels.len());
+
+        for line in code.iter() {
+            if let Some(target) = line
+                .strip_prefix("goto ")
+                .or_else(|| line.strip_prefix("if-goto "))
+            {
+                assert!(code.contains(&format!("label {}", target)), "{}", line);
+            }
+        }
+    }
+
+    #[test]
+    fn build_return_inside_nested_while_and_if() {
+        let source = "class Main { function int find(boolean c, boolean d) { while (c) { if (d) { return 1; } } return 0; } }";
+        let tokenizer = Tokenizer::new(source);
+        let tree = ClassNode::build(&tokenizer).unwrap();
+        let mut writer = VmWriter::new();
+
+        let code: Vec<String> = writer.build(&tree);
+
+        assert_eq!(
+            code,
+            vec![
+                "function Main.find 0",
+                "label WHILE_EXP0",
+                "push argument 0",
+                "not",
+                "if-goto WHILE_END0",
+                "push argument 1",
+                "if-goto IF_TRUE1",
+                "goto IF_FALSE1",
+                "label IF_TRUE1",
+                "push constant 1",
+                "return",
+                "label IF_FALSE1",
+                "goto WHILE_EXP0",
+                "label WHILE_END0",
+                "push constant 0",
+                "return",
+            ]
+        );
+    }
+
+    #[test]
+    fn build_do_on_this() {
+        let source = "class Main { method void move(int dx) { return; } method void run() { do this.move(1); do move(1); return; } method int size() { return this.size(); } }";
+        let tokenizer = Tokenizer::new(source);
+        let tree = ClassNode::build(&tokenizer).unwrap();
+        let mut writer = VmWriter::new();
+
+        let program = writer.build_program(&tree);
+
+        assert_eq!(
+            program.get_function("Main.run").unwrap().get_code(),
+            &vec![
+                "function Main.run 0",
+                "push argument 0",
+                "pop pointer 0",
+                "push pointer 0",
+                "push constant 1",
+                "call Main.move 2",
+                "pop temp 0",
+                "push pointer 0",
+                "push constant 1",
+                "call Main.move 2",
+                "pop temp 0",
+                "push constant 0",
+                "return",
+            ]
+        );
+        assert_eq!(
+            program.get_function("Main.size").unwrap().get_code(),
+            &vec![
+                "function Main.size 0",
+                "push argument 0",
+                "pop pointer 0",
+                "push pointer 0",
+                "call Main.size 1",
+                "return",
+            ]
+        );
+    }
+
+    #[test]
+    fn build_do_void_os_call() {
+        let tokenizer = Tokenizer::new("do Screen.clearScreen();");
+        let tree = Statement::build(&tokenizer).unwrap();
+
+        let mut writer = VmWriter::new();
+        let code: Vec<String> = writer.build(&tree);
+
+        assert_eq!(code, vec!["call Screen.clearScreen 0", "pop temp 0"]);
+    }
+
     #[test]
     fn build_do_with_args() {
         let tokenizer = Tokenizer::new("do print(name, age, country);");
-        let tree = Statement::build(&tokenizer);
+        let tree = Statement::build(&tokenizer).unwrap();
 
         let mut symbol_table = SymbolTable::new();
         symbol_table.add("var", "String", "name");
@@ -923,7 +2127,7 @@ mod tests {
         writer.set_class_name(String::from("TestClass"));
         let code: Vec<String> = writer.build(&tree);
 
-        assert_eq!(code.get(0).unwrap(), "push pointer 0");
+        assert_eq!(code.first().unwrap(), "push pointer 0");
         assert_eq!(code.get(1).unwrap(), "push local 0");
         assert_eq!(code.get(2).unwrap(), "push local 1");
         assert_eq!(code.get(3).unwrap(), "push local 2");
@@ -934,7 +2138,7 @@ mod tests {
     #[test]
     fn build_while() {
         let tokenizer = Tokenizer::new("while (x < 10) { let a = -1; }");
-        let tree = Statement::build(&tokenizer);
+        let tree = Statement::build(&tokenizer).unwrap();
 
         let mut symbol_table = SymbolTable::new();
         symbol_table.add("argument", "int", "x");
@@ -950,7 +2154,7 @@ mod tests {
 
         let code: Vec<String> = writer.build(&tree);
 
-        assert_eq!(code.get(0).unwrap(), "label WHILE_EXP1");
+        assert_eq!(code.first().unwrap(), "label WHILE_EXP1");
         assert_eq!(code.get(1).unwrap(), "push argument 0");
         assert_eq!(code.get(2).unwrap(), "push constant 10");
         assert_eq!(code.get(3).unwrap(), "lt");
@@ -968,7 +2172,7 @@ mod tests {
     #[test]
     fn build_if() {
         let tokenizer = Tokenizer::new("if (~exit) { do print(10); }");
-        let tree = Statement::build(&tokenizer);
+        let tree = Statement::build(&tokenizer).unwrap();
 
         let mut symbol_table = SymbolTable::new();
         symbol_table.add("var", "boolean", "exit");
@@ -983,7 +2187,7 @@ mod tests {
 
         let code: Vec<String> = writer.build(&tree);
 
-        assert_eq!(code.get(0).unwrap(), "push local 0");
+        assert_eq!(code.first().unwrap(), "push local 0");
         assert_eq!(code.get(1).unwrap(), "not");
 
         assert_eq!(code.get(2).unwrap(), "if-goto IF_TRUE1");
@@ -1001,7 +2205,7 @@ mod tests {
     #[test]
     fn build_if_else() {
         let tokenizer = Tokenizer::new("if (~exit) { do print(10); } else { do exit(); }");
-        let tree = Statement::build(&tokenizer);
+        let tree = Statement::build(&tokenizer).unwrap();
 
         let mut symbol_table = SymbolTable::new();
         symbol_table.add("var", "boolean", "exit");
@@ -1016,7 +2220,7 @@ mod tests {
 
         let code: Vec<String> = writer.build(&tree);
 
-        assert_eq!(code.get(0).unwrap(), "push local 0");
+        assert_eq!(code.first().unwrap(), "push local 0");
         assert_eq!(code.get(1).unwrap(), "not");
 
         assert_eq!(code.get(2).unwrap(), "if-goto IF_TRUE1");
@@ -1042,12 +2246,12 @@ mod tests {
     fn build_constructor() {
         let source = "class Test { field int a, b; constructor Test new(int set_a) { var boolean exit; let a = set_a; let b = 10; return this; } }";
         let tokenizer = Tokenizer::new(source);
-        let tree = ClassNode::build(&tokenizer);
+        let tree = ClassNode::build(&tokenizer).unwrap();
         let mut writer = VmWriter::new();
 
         let code: Vec<String> = writer.build(&tree);
 
-        assert_eq!(code.get(0).unwrap(), "function Test.new 1");
+        assert_eq!(code.first().unwrap(), "function Test.new 1");
         assert_eq!(code.get(1).unwrap(), "push constant 2");
         assert_eq!(code.get(2).unwrap(), "call Memory.alloc 1");
         assert_eq!(code.get(3).unwrap(), "pop pointer 0");
@@ -1062,16 +2266,242 @@ mod tests {
         assert_eq!(code.get(9).unwrap(), "return");
     }
 
+    #[test]
+    fn build_method_arguments_after_this() {
+        let source = "class Main { method int sum(int a, int b) { return a + b; } }";
+        let tokenizer = Tokenizer::new(source);
+        let tree = ClassNode::build(&tokenizer).unwrap();
+        let mut writer = VmWriter::new();
+
+        let code: Vec<String> = writer.build(&tree);
+
+        assert_eq!(
+            code,
+            vec![
+                "function Main.sum 0",
+                "push argument 0",
+                "pop pointer 0",
+                "push argument 1",
+                "push argument 2",
+                "add",
+                "return",
+            ]
+        );
+    }
+
+    #[test]
+    fn build_constructor_without_fields() {
+        let source = "class P { constructor P new() { return this; } }";
+        let tokenizer = Tokenizer::new(source);
+        let tree = ClassNode::build(&tokenizer).unwrap();
+        let mut writer = VmWriter::new();
+
+        let code: Vec<String> = writer.build(&tree);
+
+        assert_eq!(writer.get_class_metadata().get_fields(), 0);
+        assert_eq!(
+            code,
+            vec![
+                "function P.new 0",
+                "push constant 1",
+                "call Memory.alloc 1",
+                "pop pointer 0",
+                "push pointer 0",
+                "return",
+            ]
+        );
+    }
+
+    #[test]
+    fn build_constructor_with_auto_return_this() {
+        let source = "class Test { field int a; constructor Test new() { let a = 1; } }";
+        let tokenizer = Tokenizer::new(source);
+        let tree = ClassNode::build(&tokenizer).unwrap();
+        let mut writer = VmWriter::with_options(WriterOptions {
+            auto_return_this: true,
+            ..WriterOptions::default()
+        });
+
+        let code: Vec<String> = writer.build(&tree);
+
+        assert_eq!(code.len(), 8);
+        assert_eq!(code.get(5).unwrap(), "pop this 0");
+        assert_eq!(code.get(6).unwrap(), "push pointer 0");
+        assert_eq!(code.get(7).unwrap(), "return");
+    }
+
+    #[test]
+    fn build_constructor_with_auto_return_this_already_returning_this() {
+        let source =
+            "class Test { field int a; constructor Test new() { let a = 1; return this; } }";
+        let tokenizer = Tokenizer::new(source);
+        let tree = ClassNode::build(&tokenizer).unwrap();
+        let mut writer = VmWriter::with_options(WriterOptions {
+            auto_return_this: true,
+            ..WriterOptions::default()
+        });
+
+        let code: Vec<String> = writer.build(&tree);
+
+        assert_eq!(code.len(), 8);
+        assert_eq!(code.get(6).unwrap(), "push pointer 0");
+        assert_eq!(code.get(7).unwrap(), "return");
+    }
+
+    #[test]
+    fn build_class_with_end_function_marker() {
+        let source =
+            "class Main { function void main() { return; } function int run() { return 1; } }";
+        let tokenizer = Tokenizer::new(source);
+        let tree = ClassNode::build(&tokenizer).unwrap();
+        let mut writer = VmWriter::with_options(WriterOptions {
+            end_function_marker: true,
+            ..WriterOptions::default()
+        });
+
+        let code: Vec<String> = writer.build(&tree);
+
+        assert_eq!(code.len(), 8);
+        assert_eq!(code.first().unwrap(), "function Main.main 0");
+        assert_eq!(code.get(2).unwrap(), "return");
+        assert_eq!(code.get(3).unwrap(), "// end function Main.main");
+        assert_eq!(code.get(4).unwrap(), "function Main.run 0");
+        assert_eq!(code.get(6).unwrap(), "return");
+        assert_eq!(code.get(7).unwrap(), "// end function Main.run");
+    }
+
+    #[test]
+    fn build_class_with_official_label_numbering() {
+        let source = "class Main { function void main() { var int i; let i = 0; while (i < 2) { if (i = 0) { let i = 1; } else { let i = 2; } } if (i) { return; } return; } function void run() { while (true) { return; } return; } }";
+        let tokenizer = Tokenizer::new(source);
+        let tree = ClassNode::build(&tokenizer).unwrap();
+        let mut writer = VmWriter::with_options(WriterOptions {
+            label_numbering: LabelNumbering::Official,
+            ..WriterOptions::default()
+        });
+
+        let code: Vec<String> = writer.build(&tree);
+
+        assert_eq!(
+            code,
+            vec![
+                "function Main.main 1",
+                "push constant 0",
+                "pop local 0",
+                "label WHILE_EXP0",
+                "push local 0",
+                "push constant 2",
+                "lt",
+                "not",
+                "if-goto WHILE_END0",
+                "push local 0",
+                "push constant 0",
+                "eq",
+                "if-goto IF_TRUE0",
+                "goto IF_FALSE0",
+                "label IF_TRUE0",
+                "push constant 1",
+                "pop local 0",
+                "goto IF_END0",
+                "label IF_FALSE0",
+                "push constant 2",
+                "pop local 0",
+                "label IF_END0",
+                "goto WHILE_EXP0",
+                "label WHILE_END0",
+                "push local 0",
+                "if-goto IF_TRUE1",
+                "goto IF_FALSE1",
+                "label IF_TRUE1",
+                "push constant 0",
+                "return",
+                "label IF_FALSE1",
+                "push constant 0",
+                "return",
+                "function Main.run 0",
+                "label WHILE_EXP0",
+                "push constant 0",
+                "not",
+                "not",
+                "if-goto WHILE_END0",
+                "push constant 0",
+                "return",
+                "goto WHILE_EXP0",
+                "label WHILE_END0",
+                "push constant 0",
+                "return",
+            ]
+        );
+    }
+
+    #[test]
+    fn build_function_with_too_many_locals() {
+        let source = "class Main { function void main() { var int a, b; var boolean c; return; } function void run() { var int a; return; } }";
+        let tokenizer = Tokenizer::new(source);
+        let tree = ClassNode::build(&tokenizer).unwrap();
+        let mut writer = VmWriter::with_options(WriterOptions {
+            max_locals: Some(2),
+            ..WriterOptions::default()
+        });
+
+        let code: Vec<String> = writer.build(&tree);
+
+        assert_eq!(code.first().unwrap(), "function Main.main 3");
+        assert_eq!(
+            writer
+                .get_warnings()
+                .iter()
+                .map(|warning| warning.to_string())
+                .collect::<Vec<String>>(),
+            vec!["1:28: Main.main declares 3 locals, more than the limit of 2"]
+        );
+    }
+
+    #[test]
+    fn build_function_with_dedup_strings() {
+        let source = "class Main { function void main() { var int a; do Output.printString(\"Hi\"); do Output.printString(\"Ok\"); do Output.printString(\"Hi\"); return; } }";
+        let tokenizer = Tokenizer::new(source);
+        let tree = ClassNode::build(&tokenizer).unwrap();
+        let mut writer = VmWriter::with_options(WriterOptions {
+            dedup_strings: true,
+            ..WriterOptions::default()
+        });
+
+        let code: Vec<String> = writer.build(&tree);
+
+        assert_eq!(code.first().unwrap(), "function Main.main 2");
+
+        assert_eq!(code.get(1).unwrap(), "push constant 2");
+        assert_eq!(code.get(2).unwrap(), "call String.new 1");
+        assert_eq!(code.get(3).unwrap(), "push constant 72");
+        assert_eq!(code.get(4).unwrap(), "call String.appendChar 2");
+        assert_eq!(code.get(5).unwrap(), "push constant 105");
+        assert_eq!(code.get(6).unwrap(), "call String.appendChar 2");
+        assert_eq!(code.get(7).unwrap(), "pop local 1");
+
+        assert_eq!(code.get(8).unwrap(), "push local 1");
+        assert_eq!(code.get(9).unwrap(), "call Output.printString 1");
+        assert_eq!(code.get(12).unwrap(), "call String.new 1");
+        assert_eq!(code.get(19).unwrap(), "push local 1");
+        assert_eq!(code.get(20).unwrap(), "call Output.printString 1");
+
+        let constructions = code
+            .iter()
+            .filter(|line| *line == "call String.new 1")
+            .count();
+        assert_eq!(constructions, 2);
+    }
+
     #[test]
     fn build_function() {
         let source = "class Main { function void main() { var int b; var boolean exit; let b = 10; return; } }";
         let tokenizer = Tokenizer::new(source);
-        let tree = ClassNode::build(&tokenizer);
+        let tree = ClassNode::build(&tokenizer).unwrap();
         let mut writer = VmWriter::new();
 
         let code: Vec<String> = writer.build(&tree);
 
-        assert_eq!(code.get(0).unwrap(), "function Main.main 2");
+        assert_eq!(code.first().unwrap(), "function Main.main 2");
 
         assert_eq!(code.get(1).unwrap(), "push constant 10");
         assert_eq!(code.get(2).unwrap(), "pop local 0");
@@ -1084,12 +2514,12 @@ mod tests {
     fn build_method() {
         let source = "class Point { field int x; method int move(int size) { let x = x + size; return x; } }";
         let tokenizer = Tokenizer::new(source);
-        let tree = ClassNode::build(&tokenizer);
+        let tree = ClassNode::build(&tokenizer).unwrap();
         let mut writer = VmWriter::new();
 
         let code: Vec<String> = writer.build(&tree);
 
-        assert_eq!(code.get(0).unwrap(), "function Point.move 0");
+        assert_eq!(code.first().unwrap(), "function Point.move 0");
         assert_eq!(code.get(1).unwrap(), "push argument 0");
         assert_eq!(code.get(2).unwrap(), "pop pointer 0");
 
@@ -1102,16 +2532,31 @@ mod tests {
         assert_eq!(code.get(8).unwrap(), "return");
     }
 
+    #[test]
+    fn build_method_with_compound_field_update() {
+        let source = "class Counter { field int size, count; method void double() { let count = count + count; return; } }";
+        let tokenizer = Tokenizer::new(source);
+        let tree = ClassNode::build(&tokenizer).unwrap();
+        let mut writer = VmWriter::new();
+
+        let code: Vec<String> = writer.build(&tree);
+
+        assert_eq!(code.get(3).unwrap(), "push this 1");
+        assert_eq!(code.get(4).unwrap(), "push this 1");
+        assert_eq!(code.get(5).unwrap(), "add");
+        assert_eq!(code.get(6).unwrap(), "pop this 1");
+    }
+
     #[test]
     fn build_function_with_os() {
         let source = "class Main { function void main() { var int value; let value = Memory.peek(8000);  return; } }";
         let tokenizer = Tokenizer::new(source);
-        let tree = ClassNode::build(&tokenizer);
+        let tree = ClassNode::build(&tokenizer).unwrap();
         let mut writer = VmWriter::new();
 
         let code: Vec<String> = writer.build(&tree);
 
-        assert_eq!(code.get(0).unwrap(), "function Main.main 1");
+        assert_eq!(code.first().unwrap(), "function Main.main 1");
 
         assert_eq!(code.get(1).unwrap(), "push constant 8000");
         assert_eq!(code.get(2).unwrap(), "call Memory.peek 1");
@@ -1121,16 +2566,183 @@ mod tests {
         assert_eq!(code.get(5).unwrap(), "return");
     }
 
+    #[test]
+    fn build_function_with_two_os_calls() {
+        let source = "class Main { function void main() { var int v, x, y; let v = Math.sqrt(x) + Math.abs(y); return; } }";
+        let tokenizer = Tokenizer::new(source);
+        let tree = ClassNode::build(&tokenizer).unwrap();
+        let mut writer = VmWriter::new();
+
+        let code: Vec<String> = writer.build(&tree);
+
+        assert_eq!(code.first().unwrap(), "function Main.main 3");
+
+        assert_eq!(code.get(1).unwrap(), "push local 1");
+        assert_eq!(code.get(2).unwrap(), "call Math.sqrt 1");
+        assert_eq!(code.get(3).unwrap(), "push local 2");
+        assert_eq!(code.get(4).unwrap(), "call Math.abs 1");
+        assert_eq!(code.get(5).unwrap(), "add");
+        assert_eq!(code.get(6).unwrap(), "pop local 0");
+
+        assert_eq!(code.get(7).unwrap(), "push constant 0");
+        assert_eq!(code.get(8).unwrap(), "return");
+    }
+
+    #[test]
+    fn build_method_with_array_parameter() {
+        let source = "class Main { method void f(Array data) { do data.dispose(); return; } }";
+        let tokenizer = Tokenizer::new(source);
+        let tree = ClassNode::build(&tokenizer).unwrap();
+        let mut writer = VmWriter::new();
+
+        let code: Vec<String> = writer.build(&tree);
+
+        assert_eq!(code.first().unwrap(), "function Main.f 0");
+        assert_eq!(code.get(1).unwrap(), "push argument 0");
+        assert_eq!(code.get(2).unwrap(), "pop pointer 0");
+
+        assert_eq!(code.get(3).unwrap(), "push argument 1");
+        assert_eq!(code.get(4).unwrap(), "call Array.dispose 1");
+        assert_eq!(code.get(5).unwrap(), "pop temp 0");
+
+        assert_eq!(code.get(6).unwrap(), "push constant 0");
+        assert_eq!(code.get(7).unwrap(), "return");
+    }
+
+    #[test]
+    fn build_do_with_spaced_arguments() {
+        let compile = |source: &str| {
+            let tokenizer = Tokenizer::new(source);
+            let tree = Statement::build(&tokenizer).unwrap();
+
+            let mut symbol_table = SymbolTable::new();
+            symbol_table.add("var", "int", "a");
+            symbol_table.add("var", "int", "b");
+
+            let mut writer = VmWriter::new();
+            writer.set_symbol_table(symbol_table);
+            writer.set_class_name(String::from("TestClass"));
+
+            writer.build(&tree)
+        };
+
+        let code = compile("do f( a , b );");
+
+        assert_eq!(
+            code,
+            vec![
+                "push pointer 0",
+                "push local 0",
+                "push local 1",
+                "call TestClass.f 3",
+                "pop temp 0",
+            ]
+        );
+        assert_eq!(code, compile("do f(a,b);"));
+    }
+
+    #[test]
+    fn build_do_with_array_element_arguments() {
+        let source = "class Main { field Array points, colors; method void paint(int i, int j) { do draw(points[i], colors[j]); return; } }";
+        let tokenizer = Tokenizer::new(source);
+        let tree = ClassNode::build(&tokenizer).unwrap();
+        let mut writer = VmWriter::new();
+
+        let code: Vec<String> = writer.build(&tree);
+
+        assert_eq!(
+            code,
+            vec![
+                "function Main.paint 0",
+                "push argument 0",
+                "pop pointer 0",
+                "push pointer 0",
+                "push this 0",
+                "push argument 1",
+                "add",
+                "pop pointer 1",
+                "push that 0",
+                "push this 1",
+                "push argument 2",
+                "add",
+                "pop pointer 1",
+                "push that 0",
+                "call Main.draw 3",
+                "pop temp 0",
+                "push constant 0",
+                "return",
+            ]
+        );
+    }
+
+    #[test]
+    fn build_empty_void_function() {
+        let source = "class Main { function void noop() {} }";
+        let tokenizer = Tokenizer::new(source);
+        let tree = ClassNode::build(&tokenizer).unwrap();
+        let mut writer = VmWriter::new();
+
+        let code: Vec<String> = writer.build(&tree);
+
+        assert_eq!(
+            code,
+            vec!["function Main.noop 0", "push constant 0", "return"]
+        );
+    }
+
+    #[test]
+    fn build_function_returning_array() {
+        let source = "class Main { function Array make() { return null; } }";
+        let tokenizer = Tokenizer::new(source);
+        let tree = ClassNode::build(&tokenizer).unwrap();
+        let mut writer = VmWriter::new();
+
+        let code: Vec<String> = writer.build(&tree);
+
+        assert_eq!(
+            code,
+            vec!["function Main.make 0", "push constant 0", "return"]
+        );
+    }
+
+    #[test]
+    fn build_method_calling_local_methods() {
+        let source = "class Main { method void a() { do b(); do c(1, 2); return; } method void b() { return; } method void c(int x, int y) { return; } }";
+        let tokenizer = Tokenizer::new(source);
+        let tree = ClassNode::build(&tokenizer).unwrap();
+        let mut writer = VmWriter::new();
+
+        let code: Vec<String> = writer.build(&tree);
+
+        assert_eq!(
+            code[..12].to_vec(),
+            vec![
+                "function Main.a 0",
+                "push argument 0",
+                "pop pointer 0",
+                "push pointer 0",
+                "call Main.b 1",
+                "pop temp 0",
+                "push pointer 0",
+                "push constant 1",
+                "push constant 2",
+                "call Main.c 3",
+                "pop temp 0",
+                "push constant 0",
+            ]
+        );
+    }
+
     #[test]
     fn build_function_with_instance() {
         let source = "class Main { function void main() { var Point value; let value = Point.new(); do value.sum(800); return; } }";
         let tokenizer = Tokenizer::new(source);
-        let tree = ClassNode::build(&tokenizer);
+        let tree = ClassNode::build(&tokenizer).unwrap();
         let mut writer = VmWriter::new();
 
         let code: Vec<String> = writer.build(&tree);
 
-        assert_eq!(code.get(0).unwrap(), "function Main.main 1");
+        assert_eq!(code.first().unwrap(), "function Main.main 1");
 
         assert_eq!(code.get(1).unwrap(), "call Point.new 0");
         assert_eq!(code.get(2).unwrap(), "pop local 0");
@@ -1148,12 +2760,12 @@ mod tests {
     fn build_call_with_local_method_call() {
         let source = "class Main { function void main() { do print(); return; } method void print() {return;} }";
         let tokenizer = Tokenizer::new(source);
-        let tree = ClassNode::build(&tokenizer);
+        let tree = ClassNode::build(&tokenizer).unwrap();
         let mut writer = VmWriter::new();
 
         let code: Vec<String> = writer.build(&tree);
 
-        assert_eq!(code.get(0).unwrap(), "function Main.main 0");
+        assert_eq!(code.first().unwrap(), "function Main.main 0");
 
         assert_eq!(code.get(1).unwrap(), "push pointer 0");
         assert_eq!(code.get(2).unwrap(), "call Main.print 1");
@@ -1167,12 +2779,12 @@ mod tests {
     fn build_call_let_with_local_method_call() {
         let source = "class Main { function void main() { var int x; let x = ten(); return; } method int ten() { return 10; } }";
         let tokenizer = Tokenizer::new(source);
-        let tree = ClassNode::build(&tokenizer);
+        let tree = ClassNode::build(&tokenizer).unwrap();
         let mut writer = VmWriter::new();
 
         let code: Vec<String> = writer.build(&tree);
 
-        assert_eq!(code.get(0).unwrap(), "function Main.main 1");
+        assert_eq!(code.first().unwrap(), "function Main.main 1");
 
         assert_eq!(code.get(1).unwrap(), "push pointer 0");
         assert_eq!(code.get(2).unwrap(), "call Main.ten 1");
@@ -0,0 +1,484 @@
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+
+fn write_source(test_name: &str, source: &str) -> PathBuf {
+    let folder = env::temp_dir().join(format!("jack_cli_{}_{}", test_name, std::process::id()));
+    fs::create_dir_all(&folder).unwrap();
+
+    let filename = folder.join("Main.jack");
+    fs::write(&filename, source).unwrap();
+
+    filename
+}
+
+fn run(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_jack_compiler"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+fn run_with_stdin(args: &[&str], input: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_jack_compiler"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+
+    child.wait_with_output().unwrap()
+}
+
+const DEAD_LOOP: &str =
+    "class Main { function void main() { while (false) { do Main.main(); } return; } }";
+
+#[test]
+fn bootstrap_is_written_once_for_a_folder() {
+    let main = write_source(
+        "bootstrap",
+        "class Main { function void main() { do Other.run(); return; } }",
+    );
+    let folder = main.parent().unwrap();
+    fs::write(
+        folder.join("Other.jack"),
+        "class Other { function void run() { return; } }",
+    )
+    .unwrap();
+    let bootstrap = env::temp_dir().join(format!("jack_cli_bootstrap_{}.vm", std::process::id()));
+
+    let output = run(&[
+        folder.to_str().unwrap(),
+        "--bootstrap",
+        bootstrap.to_str().unwrap(),
+        "--entry",
+        "Main.main",
+    ]);
+    let merged = fs::read_to_string(&bootstrap).unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(merged.matches("call Main.main 0").count(), 1);
+    assert!(merged.starts_with("// bootstrap\r\ncall Main.main 0\r\n"));
+    assert!(merged.contains("function Main.main 0"));
+    assert!(merged.contains("function Other.run 0"));
+
+    fs::remove_file(bootstrap).unwrap();
+    fs::remove_dir_all(folder).unwrap();
+}
+
+#[test]
+fn folder_writes_one_vm_file_per_class() {
+    let main = write_source(
+        "vm_per_class",
+        "class Main { function void main() { do Other.run(); return; } }",
+    );
+    let folder = main.parent().unwrap();
+    fs::write(
+        folder.join("Other.jack"),
+        "class Other { function void run() { return; } }",
+    )
+    .unwrap();
+
+    let output = run(&[folder.to_str().unwrap()]);
+
+    assert!(output.status.success());
+    assert_eq!(
+        fs::read_to_string(folder.join("Main.vm")).unwrap(),
+        "function Main.main 0\r\ncall Other.run 0\r\npop temp 0\r\npush constant 0\r\nreturn"
+    );
+    assert_eq!(
+        fs::read_to_string(folder.join("Other.vm")).unwrap(),
+        "function Other.run 0\r\npush constant 0\r\nreturn"
+    );
+
+    fs::remove_dir_all(folder).unwrap();
+}
+
+#[test]
+fn fast_writes_the_same_vm_as_the_default() {
+    let filename = write_source(
+        "fast",
+        "class Main { function void main() { var int x; let x = 1 + 2; do Output.printInt(x); return; } }",
+    );
+    let folder = filename.parent().unwrap();
+    let expected = "function Main.main 1\r\npush constant 1\r\npush constant 2\r\nadd\r\npop local 0\r\npush local 0\r\ncall Output.printInt 1\r\npop temp 0\r\npush constant 0\r\nreturn";
+    let checks = [
+        filename.to_str().unwrap(),
+        "--lint",
+        "--lint-naming",
+        "--unresolved",
+        "--max-locals",
+        "8",
+    ];
+
+    let output = run(&checks);
+
+    assert!(output.status.success());
+    assert_eq!(
+        fs::read_to_string(folder.join("Main.vm")).unwrap(),
+        expected
+    );
+
+    fs::remove_file(folder.join("Main.vm")).unwrap();
+
+    let output = run(&[&checks[..], &["--fast"]].concat());
+
+    assert!(output.status.success());
+    assert_eq!(
+        fs::read_to_string(folder.join("Main.vm")).unwrap(),
+        expected
+    );
+
+    fs::remove_dir_all(folder).unwrap();
+}
+
+#[test]
+fn unresolved_classes_of_a_folder() {
+    let main = write_source(
+        "unresolved",
+        "class Main { function void main() { do Other.run(); do Helper.help(); do Output.println(); return; } }",
+    );
+    let folder = main.parent().unwrap();
+    fs::write(
+        folder.join("Other.jack"),
+        "class Other { function void run() { return; } }",
+    )
+    .unwrap();
+
+    let output = run(&[folder.to_str().unwrap(), "--unresolved", "--no-color"]);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(
+        stderr.lines().collect::<Vec<&str>>(),
+        vec!["warning: class 'Helper' is used but never defined"]
+    );
+
+    fs::remove_dir_all(folder).unwrap();
+}
+
+#[test]
+fn non_jack_file_is_rejected() {
+    let main = write_source("non_jack", "class Main { }");
+    let folder = main.parent().unwrap();
+    let notes = folder.join("notes.txt");
+    fs::write(&notes, "class Main { }").unwrap();
+
+    let output = run(&[notes.to_str().unwrap(), "--no-color"]);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        stderr.trim_end(),
+        format!("{}: error: not a .jack file or a folder", notes.display())
+    );
+    assert!(!folder.join("Main.vm").exists());
+
+    fs::remove_dir_all(folder).unwrap();
+}
+
+#[test]
+fn invalid_utf8_file_is_reported_and_skipped() {
+    let main = write_source(
+        "invalid_utf8",
+        "class Main { function void main() { return; } }",
+    );
+    let folder = main.parent().unwrap();
+    fs::write(folder.join("Bad.jack"), b"class Bad { \xff\xfe }").unwrap();
+
+    let output = run(&[folder.to_str().unwrap()]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    assert!(!output.status.success());
+    assert!(stderr.contains("error: file is not valid UTF-8:"));
+    assert!(stderr.contains("Bad.jack"));
+    assert!(!stderr.contains("panicked"));
+    assert!(main.with_extension("vm").exists());
+
+    fs::remove_dir_all(folder).unwrap();
+}
+
+#[test]
+fn lint_warning_keeps_exit_code_zero() {
+    let filename = write_source("warning", DEAD_LOOP);
+
+    let output = run(&[filename.to_str().unwrap(), "--lint"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    assert!(output.status.success());
+    assert!(stderr.contains("warning: 1:37: always false loop body is dead"));
+    assert!(filename.with_extension("vm").exists());
+
+    fs::remove_dir_all(filename.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn no_color_keeps_diagnostics_plain() {
+    let filename = write_source("no_color", DEAD_LOOP);
+
+    let output = run(&[filename.to_str().unwrap(), "--lint", "--no-color"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    assert!(stderr.contains("warning: 1:37: always false loop body is dead"));
+    assert!(!stderr.contains('\x1b'));
+
+    let output = run(&[filename.to_str().unwrap(), "--lint", "--color"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    assert!(stderr.contains("\x1b[33mwarning:\x1b[0m 1:37: always false loop body is dead"));
+
+    fs::remove_dir_all(filename.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn lint_warning_fails_with_warnings_as_errors() {
+    let filename = write_source("warnings_as_errors", DEAD_LOOP);
+
+    let output = run(&[filename.to_str().unwrap(), "--lint", "--warnings-as-errors"]);
+
+    assert!(!output.status.success());
+
+    fs::remove_dir_all(filename.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn stdin_errors_use_the_stdin_name() {
+    let source = "class Main { function void main() { do Main; return; } }";
+
+    let output = run_with_stdin(&["-", "--stdin-name", "Main.jack"], source);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    assert!(!output.status.success());
+    assert!(stderr.contains("Main.jack: error: 1:44: expected '(' or '.'"));
+
+    let output = run_with_stdin(&["-"], source);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    assert!(stderr.contains("<stdin>: error: 1:44:"));
+}
+
+#[test]
+fn stdin_is_compiled_to_stdout() {
+    let output = run_with_stdin(&["-"], "class Main { function void main() { return; } }");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    assert!(output.status.success());
+    assert_eq!(
+        stdout.lines().collect::<Vec<&str>>(),
+        vec!["function Main.main 0", "push constant 0", "return"]
+    );
+}
+
+#[test]
+fn repl_keeps_reading_after_a_truncated_line() {
+    let output = run_with_stdin(
+        &["--repl"],
+        "return\nlet x\ndo f\ndo Output.printInt\n1 + 2\n",
+    );
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    assert!(output.status.success());
+    assert!(!stderr.contains("panicked"));
+    assert_eq!(
+        stdout.lines().collect::<Vec<&str>>(),
+        vec![
+            "error: 1:1: unexpected end of file while parsing return",
+            "error: 1:5: unexpected end of file while parsing let",
+            "error: 1:4: unexpected end of file while parsing subroutine call",
+            "error: 1:11: unexpected end of file while parsing subroutine call",
+            "push constant 1",
+            "push constant 2",
+            "add",
+        ]
+    );
+}
+
+#[test]
+fn syntax_error_is_reported_with_error_prefix() {
+    let filename = write_source(
+        "error",
+        "class Main { function void main() { do Main; return; } }",
+    );
+
+    let output = run(&[filename.to_str().unwrap()]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    assert!(!output.status.success());
+    assert!(stderr.contains(
+        "error: 1:44: expected '(' or '.' after identifier in subroutine call, found ';'"
+    ));
+
+    fs::remove_dir_all(filename.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn missing_expression_is_reported_without_panicking() {
+    let sources = [
+        (
+            "var int x; let x = ; return;",
+            "1:56: expected an expression, found ';'",
+        ),
+        (
+            "var int x; let x = * 2; return;",
+            "1:56: expected an expression, found '*'",
+        ),
+        ("return 1 + ;", "1:48: expected an expression, found ';'"),
+        ("return", "1:44: expected an expression, found '}'"),
+        (
+            "var int x; let x = int; return;",
+            "1:56: expected an expression, found 'int'",
+        ),
+    ];
+
+    for (body, message) in sources.iter() {
+        let filename = write_source(
+            "missing_expression",
+            format!("class Main {{ function void main() {{ {} }} }}", body).as_str(),
+        );
+
+        let output = run(&[filename.to_str().unwrap()]);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+
+        assert_eq!(output.status.code(), Some(1));
+        assert!(!stderr.contains("panicked"));
+        assert!(stderr.contains(format!("error: {}", message).as_str()));
+
+        fs::remove_dir_all(filename.parent().unwrap()).unwrap();
+    }
+}
+
+#[test]
+fn token_over_the_limit_is_an_error() {
+    let filename = write_source(
+        "token_limit",
+        "class Main { function void main() { var int counter; return; } }",
+    );
+
+    let output = run(&[filename.to_str().unwrap(), "--max-token-length", "5"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr.contains("error: 1:14: token is 8 characters long, more than the limit of 5"));
+
+    fs::remove_dir_all(filename.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn invalid_identifier_is_reported_with_its_position() {
+    let filename = write_source(
+        "invalid_identifier",
+        "class Main { function void main() { var int x; let x = 2foo; return; } }",
+    );
+
+    let output = run(&[filename.to_str().unwrap()]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr.contains("error: 1:56: invalid identifier '2foo'"));
+    assert!(!stderr.contains("panicked"));
+
+    fs::remove_dir_all(filename.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn syntax_error_does_not_stop_the_folder() {
+    let main = write_source(
+        "folder_error",
+        "class Main { function void main() { return; } }",
+    );
+    let folder = main.parent().unwrap();
+    fs::write(
+        folder.join("Bad.jack"),
+        "class Bad { function void run() { do Bad; return; } }",
+    )
+    .unwrap();
+    fs::write(
+        folder.join("Other.jack"),
+        "class Other { function void run() { return; } }",
+    )
+    .unwrap();
+
+    let output = run(&[folder.to_str().unwrap()]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr.contains("Bad.jack: error:"));
+    assert!(main.with_extension("vm").exists());
+    assert!(folder.join("Other.vm").exists());
+    assert!(!folder.join("Bad.vm").exists());
+
+    fs::remove_dir_all(folder).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn file_name_not_in_utf8_is_skipped() {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+
+    let main = write_source(
+        "non_utf8_name",
+        "class Main { function void main() { return; } }",
+    );
+    let folder = main.parent().unwrap();
+    fs::write(
+        folder.join(OsStr::from_bytes(b"Bad\xff.jack")),
+        "class Bad { }",
+    )
+    .unwrap();
+
+    let output = run(&[folder.to_str().unwrap()]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    assert!(output.status.success());
+    assert!(!stderr.contains("panicked"));
+    assert!(stderr.contains("warning: skipped"));
+    assert!(stderr.contains("file name is not valid UTF-8"));
+    assert!(main.with_extension("vm").exists());
+
+    fs::remove_dir_all(folder).unwrap();
+}
+
+#[test]
+fn lint_whitespace_warns_about_mixed_indentation() {
+    let filename = write_source(
+        "lint_whitespace",
+        "class Main {\n \tfunction void main() {\n        return;\n    }\n}",
+    );
+
+    let output = run(&[filename.to_str().unwrap(), "--lint-whitespace"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    assert!(output.status.success());
+    assert!(stderr.contains("warning: 2:1: indentation mixes tabs and spaces"));
+
+    fs::remove_dir_all(filename.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn token_cache_is_ignored_with_another_tab_width() {
+    let filename = write_source(
+        "token_cache_tab_width",
+        "class Main {\n\tfunction void main() { do Main; return; } }",
+    );
+    let path = filename.to_str().unwrap();
+
+    let output = run(&[path, "--token-cache"]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("error: 2:32:"));
+
+    let output = run(&[path, "--token-cache", "--tab-width", "4"]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("error: 2:35:"));
+
+    fs::remove_dir_all(filename.parent().unwrap()).unwrap();
+}
@@ -0,0 +1,114 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use jack_compiler::builder::build_content;
+use jack_compiler::parser::ClassNode;
+use jack_compiler::tokenizer::Tokenizer;
+use jack_compiler::writer::VmWriter;
+
+fn fixtures_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("fixtures")
+}
+
+fn list_jack_files(dir: &Path) -> Vec<PathBuf> {
+    let mut result = Vec::new();
+
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+
+        if path.is_dir() {
+            result.extend(list_jack_files(&path));
+        } else if path.extension() == Some("jack".as_ref()) {
+            result.push(path);
+        }
+    }
+
+    result.sort();
+    result
+}
+
+fn compile(path: &Path) -> Vec<String> {
+    let content = fs::read_to_string(path).unwrap();
+    let tokenizer = Tokenizer::new(&build_content(content));
+
+    let tree = ClassNode::build(&tokenizer)
+        .unwrap_or_else(|error| panic!("{}: {}", path.display(), error));
+
+    VmWriter::new().build(&tree)
+}
+
+#[test]
+fn compile_all_fixtures() {
+    let files = list_jack_files(&fixtures_dir());
+
+    assert!(files.len() >= 2);
+
+    for file in files {
+        let code = compile(&file);
+        let class_name = file.file_stem().unwrap().to_str().unwrap();
+
+        assert!(
+            code.first()
+                .unwrap()
+                .starts_with(format!("function {}.", class_name).as_str()),
+            "{}: unexpected first command {}",
+            file.display(),
+            code.first().unwrap()
+        );
+        assert_eq!(code.last().unwrap(), "return", "{}", file.display());
+    }
+}
+
+#[test]
+fn compile_seven_fixture() {
+    let code = compile(&fixtures_dir().join("Seven").join("Main.jack"));
+
+    assert_eq!(
+        code,
+        vec![
+            "function Main.main 0",
+            "push constant 1",
+            "push constant 2",
+            "push constant 3",
+            "call Math.multiply 2",
+            "add",
+            "call Output.printInt 1",
+            "pop temp 0",
+            "push constant 0",
+            "return",
+        ]
+    );
+}
+
+#[test]
+fn compile_array_use_fixture() {
+    let code = compile(&fixtures_dir().join("ArrayUse").join("Main.jack"));
+
+    assert_eq!(
+        code,
+        vec![
+            "function Main.main 2",
+            "push constant 5",
+            "call Array.new 1",
+            "pop local 0",
+            "push local 0",
+            "push constant 0",
+            "add",
+            "push constant 10",
+            "pop temp 0",
+            "pop pointer 1",
+            "push temp 0",
+            "pop that 0",
+            "push local 0",
+            "push constant 0",
+            "add",
+            "pop pointer 1",
+            "push that 0",
+            "pop local 1",
+            "push constant 0",
+            "return",
+        ]
+    );
+}